@@ -7,7 +7,6 @@ use structopt::StructOpt;
 use iced::{Application, Settings};
 use nestadia::Emulator;
 
-mod rgb_value_table;
 mod sdl_window;
 
 #[cfg(feature = "debugger")]
@@ -3,9 +3,9 @@ use std::{
     time::{Duration, Instant},
 };
 
+use nestadia::RGB_VALUE_TABLE;
 use sdl2::{event::Event, keyboard::Keycode};
 
-use super::rgb_value_table::RGB_VALUE_TABLE;
 use super::{EmulationState, NES_HEIGHT, NES_WIDTH};
 
 pub(crate) fn start_game(emulation_state: Arc<RwLock<EmulationState>>) {
@@ -130,10 +130,10 @@ pub(crate) fn start_game(emulation_state: Arc<RwLock<EmulationState>>) {
 
             // Maps 6 bit colors to RGB
             frame
-                .iter()
+                .pixels()
                 .flat_map(|c| {
                     RGB_VALUE_TABLE
-                        .get(*c as usize)
+                        .get(c as usize)
                         .unwrap_or(&[0x00, 0x00, 0x00])
                 })
                 .copied()
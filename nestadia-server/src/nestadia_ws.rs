@@ -217,7 +217,8 @@ fn start_emulation(
                     break frame;
                 }
             }
-            .to_vec();
+            .pixels()
+            .collect::<Vec<u8>>();
 
             if Instant::now() < next_frame_time {
                 std::thread::sleep(next_frame_time.duration_since(Instant::now()));
@@ -10,10 +10,6 @@ use yew::{
 };
 use yew::{virtual_dom::VNode, ChangeData};
 
-mod rgb_value_table;
-
-use rgb_value_table::RGB_VALUE_TABLE;
-
 bitflags! {
     #[derive(Default)]
     struct ControllerState: u8 {
@@ -177,23 +173,17 @@ impl Component for EmulatorComponent {
                     .unwrap();
 
                 // Convert to RGBA
-                let mut rgba_frame = vec![0u8; 256 * 240 * 4];
-
-                for i in 0..256 * 240 {
-                    let rgb = RGB_VALUE_TABLE
-                        .get(frame[i] as usize)
-                        .unwrap_or(&[0x00, 0x00, 0x00]);
-
-                    rgba_frame[i * 4] = rgb[0];
-                    rgba_frame[i * 4 + 1] = rgb[1];
-                    rgba_frame[i * 4 + 2] = rgb[2];
-                    rgba_frame[i * 4 + 3] = 0xFF;
-                }
+                let (width, height) = (frame.width(), frame.height());
+                let mut rgba_frame = vec![0u8; width * height * 4];
+                frame.to_rgba(&mut rgba_frame).unwrap();
 
                 // Draw image data to the canvas
-                let image_data =
-                    ImageData::new_with_u8_clamped_array_and_sh(Clamped(&rgba_frame), 256, 240)
-                        .unwrap();
+                let image_data = ImageData::new_with_u8_clamped_array_and_sh(
+                    Clamped(&rgba_frame),
+                    width as u32,
+                    height as u32,
+                )
+                .unwrap();
 
                 context.put_image_data(&image_data, 0.0, 0.0).unwrap();
 
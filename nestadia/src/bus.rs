@@ -1,18 +1,21 @@
 use crate::cartridge::Cartridge;
-use crate::cartridge::Mirroring;
+use crate::cartridge::{Mirroring, NametableSource};
+use crate::controller::ControllerPort;
 use crate::Ppu;
+#[cfg(feature = "debugger")]
+use crate::RamWatch;
 use crate::RAM_SIZE;
 
+/// CPU cycles suspended by an OAM DMA started on an even CPU cycle, one more on odd cycles
+const OAM_DMA_STALL_CYCLES: u16 = 513;
+
 macro_rules! borrow_cpu_bus {
     ($owner:ident) => {{
         $crate::bus::CpuBus::borrow(
-            &mut $owner.controller1,
-            &mut $owner.controller2,
-            &mut $owner.controller1_state,
-            &mut $owner.controller2_state,
-            &mut $owner.controller1_snapshot,
-            &mut $owner.controller2_snapshot,
+            &mut $owner.controller_ports,
             &mut $owner.ram,
+            &mut $owner.dma_stall_cycles,
+            &$owner.elapsed_cycles,
             &mut $owner.cartridge,
             &mut $owner.ppu,
             &mut $owner.name_tables,
@@ -27,50 +30,62 @@ macro_rules! borrow_ppu_bus {
 }
 
 pub struct CpuBus<'a> {
-    controller1: &'a mut u8,
-    controller2: &'a mut u8,
-    controller1_state: &'a mut bool,
-    controller2_state: &'a mut bool,
-    controller1_snapshot: &'a mut u8,
-    controller2_snapshot: &'a mut u8,
+    controller_ports: &'a mut [ControllerPort; 2],
     ram: &'a mut [u8; RAM_SIZE as usize],
+    dma_stall_cycles: &'a mut u16,
+    elapsed_cycles: &'a u64,
     cartridge: &'a mut Cartridge,
     ppu: &'a mut Ppu,
     name_tables: &'a mut [u8; 1024 * 4],
+    #[cfg(feature = "debugger")]
+    ram_watches: Option<&'a mut [RamWatch]>,
 }
 
 impl<'a> CpuBus<'a> {
     #[allow(clippy::too_many_arguments)] // it's fine, it's used by a macro
     pub fn borrow(
-        controller1: &'a mut u8,
-        controller2: &'a mut u8,
-        controller1_state: &'a mut bool,
-        controller2_state: &'a mut bool,
-        controller1_snapshot: &'a mut u8,
-        controller2_snapshot: &'a mut u8,
+        controller_ports: &'a mut [ControllerPort; 2],
         ram: &'a mut [u8; RAM_SIZE as usize],
+        dma_stall_cycles: &'a mut u16,
+        elapsed_cycles: &'a u64,
         cartridge: &'a mut Cartridge,
         ppu: &'a mut Ppu,
         name_tables: &'a mut [u8; 1024 * 4],
     ) -> Self {
         Self {
-            controller1,
-            controller2,
-            controller1_state,
-            controller2_state,
-            controller1_snapshot,
-            controller2_snapshot,
+            controller_ports,
             ram,
+            dma_stall_cycles,
+            elapsed_cycles,
             cartridge,
             ppu,
             name_tables,
+            #[cfg(feature = "debugger")]
+            ram_watches: None,
         }
     }
+
+    #[cfg(feature = "debugger")]
+    pub fn set_ram_watches(&mut self, ram_watches: &'a mut [RamWatch]) {
+        self.ram_watches = Some(ram_watches);
+    }
 }
 
 impl CpuBus<'_> {
     pub fn write_ram(&mut self, addr: u16, data: u8) {
-        self.ram[(addr & (RAM_SIZE - 1)) as usize] = data;
+        let addr = addr & (RAM_SIZE - 1);
+
+        #[cfg(feature = "debugger")]
+        if let Some(watches) = self.ram_watches.as_deref_mut().filter(|w| !w.is_empty()) {
+            let old = self.ram[addr as usize];
+            for (watched, callback) in watches.iter_mut() {
+                if *watched == addr {
+                    callback(addr, old, data);
+                }
+            }
+        }
+
+        self.ram[addr as usize] = data;
     }
 
     pub fn read_ram(&mut self, addr: u16) -> u8 {
@@ -78,6 +93,9 @@ impl CpuBus<'_> {
     }
 
     pub fn write_ppu_register(&mut self, addr: u16, data: u8) {
+        #[cfg(feature = "debugger")]
+        self.ppu.notify_register_write(addr, data);
+
         let mut ppu_bus = borrow_ppu_bus!(self);
         self.ppu.write(&mut ppu_bus, addr, data);
     }
@@ -88,34 +106,26 @@ impl CpuBus<'_> {
         self.ppu.read(&mut ppu_bus, addr)
     }
 
-    pub fn controller1_write(&mut self, data: u8) {
-        *self.controller1_state = data & 0x01 == 0x01;
-        *self.controller1_snapshot = *self.controller1;
-    }
-
-    pub fn read_controller1_snapshot(&mut self) -> u8 {
-        if *self.controller1_state {
-            *self.controller1 & 0x80 >> 7
-        } else {
-            let data = (*self.controller1_snapshot & 0x80) >> 7;
-            *self.controller1_snapshot <<= 1;
-            data
+    /// Write on $4016, which strobes the devices of both ports
+    pub fn controller_write(&mut self, data: u8) {
+        for port in self.controller_ports.iter_mut() {
+            port.write(data);
         }
     }
 
-    pub fn controller2_write(&mut self, data: u8) {
-        *self.controller2_state = data & 0x01 == 0x01;
-        *self.controller2_snapshot = *self.controller2;
+    /// Reads the device plugged in `port` (0 for $4016, 1 for $4017)
+    pub fn read_controller(&mut self, port: usize) -> u8 {
+        self.controller_ports[port].read()
     }
 
-    pub fn read_controller2_snapshot(&mut self) -> u8 {
-        if *self.controller2_state {
-            *self.controller2 & 0x80 >> 7
+    /// Reads $4016: controller 1, and the microphone of the Famicom controller 2
+    pub fn read_controller1(&mut self) -> u8 {
+        let microphone = if self.controller_ports[1].microphone() {
+            0x04
         } else {
-            let data = (*self.controller2_snapshot & 0x80) >> 7;
-            *self.controller2_snapshot <<= 1;
-            data
-        }
+            0x00
+        };
+        self.read_controller(0) | microphone
     }
 
     pub fn write_prg_mem(&mut self, addr: u16, data: u8) {
@@ -123,11 +133,20 @@ impl CpuBus<'_> {
     }
 
     pub fn read_prg_mem(&mut self, addr: u16) -> u8 {
-        self.cartridge.read_prg_mem(addr)
+        self.cartridge.cpu_read_prg_mem(addr)
     }
 
     pub fn write_ppu_oam_dma(&mut self, buffer: &[u8; 256]) {
         self.ppu.write_oam_dma(buffer);
+
+        // As per nesdev wiki:
+        // "The CPU is suspended during the transfer,
+        // which will take 513 or 514 cycles after the $4014 write tick.
+        // (1 wait state cycle while waiting for writes to complete,
+        // +1 if on an odd CPU cycle, then 256 alternating read/write cycles.)"
+        // Games time their vblank work around this stall, running it for free would
+        // give them more CPU time per frame than the console has
+        *self.dma_stall_cycles = OAM_DMA_STALL_CYCLES + (*self.elapsed_cycles % 2) as u16;
     }
 }
 
@@ -160,11 +179,25 @@ impl PpuBus<'_> {
     }
 
     pub fn read_name_tables(&mut self, addr: u16) -> u8 {
-        self.name_tables[self.mirror_name_tables_addr(addr) as usize]
+        match self.cartridge.nametable_source(addr) {
+            NametableSource::Mirroring => {
+                self.name_tables[self.mirror_name_tables_addr(addr) as usize]
+            }
+            NametableSource::Vram(page) => self.name_tables[page * 0x400 + (addr & 0x3FF) as usize],
+            NametableSource::Mapper => self.cartridge.read_nametable(addr),
+        }
     }
 
     pub fn write_name_tables(&mut self, addr: u16, data: u8) {
-        self.name_tables[self.mirror_name_tables_addr(addr) as usize] = data;
+        match self.cartridge.nametable_source(addr) {
+            NametableSource::Mirroring => {
+                self.name_tables[self.mirror_name_tables_addr(addr) as usize] = data
+            }
+            NametableSource::Vram(page) => {
+                self.name_tables[page * 0x400 + (addr & 0x3FF) as usize] = data
+            }
+            NametableSource::Mapper => self.cartridge.write_nametable(addr, data),
+        }
     }
 
     // http://wiki.nesdev.com/w/index.php/Mirroring#Nametable_Mirroring
@@ -202,7 +235,7 @@ impl PpuBus<'_> {
         }
     }
 
-    pub fn irq_scanline(&mut self) {
-        self.cartridge.irq_scanline();
+    pub fn irq_scanline(&mut self, scanline: i16) {
+        self.cartridge.irq_scanline(scanline);
     }
 }
@@ -4,7 +4,7 @@ use core::convert::TryFrom;
 
 use bitflags::bitflags;
 
-use crate::cartridge::RomParserError;
+use crate::cartridge::{Region, RomParserError};
 
 #[derive(Debug)]
 pub struct INesHeader {
@@ -16,6 +16,12 @@ pub struct INesHeader {
     pub flags8: u8, // Flags 8 is actually the PRG ram size
     pub flags9: Flags9,
     pub flags10: Flags10,
+    /// Total PRG RAM size declared by a NES 2.0 header, in bytes
+    pub prg_ram_size: Option<usize>,
+    /// Total CHR RAM size declared by a NES 2.0 header, in bytes
+    pub chr_ram_size: Option<usize>,
+    /// Timing declared by a NES 2.0 header, iNES headers use the TV system bit of flags 9
+    pub region: Option<Region>,
 }
 
 bitflags! {
@@ -75,6 +81,22 @@ impl TryFrom<&[u8]> for INesHeader {
         let flags9 = Flags9::from_bits_truncate(data[9]);
         let flags10 = Flags10::from_bits_truncate(data[10]);
 
+        // NES 2.0 stores volatile and non-volatile PRG RAM sizes as shift counts in byte 10,
+        // and CHR RAM sizes the same way in byte 11
+        let nes2 = data[7] & 0x0C == 0x08;
+        let shift_size = |shift: u8| if shift == 0 { 0 } else { 64usize << shift };
+        let ram_size = |byte: u8| Some(shift_size(byte & 0x0F) + shift_size(byte >> 4));
+        let prg_ram_size = if nes2 { ram_size(data[10]) } else { None };
+        let chr_ram_size = if nes2 { ram_size(data[11]) } else { None };
+
+        // NES 2.0 stores the timing in byte 12: NTSC, PAL, multi-region or Dendy.
+        // Dendy runs at 50 Hz, close to PAL, and multi-region carts run fine as NTSC.
+        let region = match (nes2, data[12] & 0x03) {
+            (false, _) => None,
+            (true, 1) | (true, 3) => Some(Region::Pal),
+            (true, _) => Some(Region::Ntsc),
+        };
+
         Ok(INesHeader {
             mapper_id,
             prg_size,
@@ -84,6 +106,9 @@ impl TryFrom<&[u8]> for INesHeader {
             flags8,
             flags9,
             flags10,
+            prg_ram_size,
+            chr_ram_size,
+            region,
         })
     }
 }
@@ -1,3 +1,6 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
 use super::{CartridgeReadTarget, Mapper, Mirroring};
 
 const CHR_MODE_MASK: u8 = 0b10000;
@@ -11,16 +14,22 @@ pub struct Mapper001 {
     chr_bank_selector_8: u8,
     chr_bank_selector_4_lo: u8,
     chr_bank_selector_4_hi: u8,
+    chr_bank_0_register: u8,
     load_register: u8,
     load_register_count: u8,
     control_register: u8,
-    ram_data: [u8; 0x2000],
+    ram_data: Vec<u8>,
     mirroring: Mirroring,
 }
 
 impl Mapper001 {
-    pub fn new(prg_banks: u8, mirroring: Mirroring, save_data: Option<&[u8]>) -> Self {
-        let mut ram_data = [0u8; 0x2000];
+    pub fn new(
+        prg_banks: u8,
+        prg_ram_size: usize,
+        mirroring: Mirroring,
+        save_data: Option<&[u8]>,
+    ) -> Self {
+        let mut ram_data = vec![0u8; prg_ram_size];
 
         // Load the save data
         if let Some(save_data) = save_data {
@@ -38,6 +47,7 @@ impl Mapper001 {
             chr_bank_selector_8: 0,
             chr_bank_selector_4_lo: 0,
             chr_bank_selector_4_hi: 0,
+            chr_bank_0_register: 0,
             load_register: 0,
             load_register_count: 0,
             control_register: 0x0C,
@@ -45,6 +55,16 @@ impl Mapper001 {
             mirroring,
         }
     }
+
+    fn ram_addr(&self, addr: u16) -> usize {
+        // SOROM and SXROM select the 8K PRG RAM bank through the CHR bank 0 register
+        let bank = match self.ram_data.len() / 0x2000 {
+            4 => (self.chr_bank_0_register >> 2) & 0x03,
+            2 => (self.chr_bank_0_register >> 3) & 0x01,
+            _ => 0,
+        };
+        (bank as usize) * 0x2000 + (addr & 0x1FFF) as usize
+    }
 }
 
 impl Mapper for Mapper001 {
@@ -52,8 +72,7 @@ impl Mapper for Mapper001 {
         match addr {
             0x6000..=0x7FFF => {
                 // Read from RAM
-                CartridgeReadTarget::PrgRam(self.ram_data[(addr & 0x1FFF) as usize])
-                // TODO: windowed RAM?
+                CartridgeReadTarget::PrgRam(self.ram_data[self.ram_addr(addr)])
             }
             _ => {
                 if (self.control_register & PRG_MODE_MASK) > 1 {
@@ -82,7 +101,8 @@ impl Mapper for Mapper001 {
     fn cpu_map_write(&mut self, addr: u16, data: u8) {
         if (0x6000..=0x7FFF).contains(&addr) {
             // Write to RAM
-            self.ram_data[(addr & 0x1FFF) as usize] = data; // TODO: windowed RAM?
+            let ram_addr = self.ram_addr(addr);
+            self.ram_data[ram_addr] = data;
             return;
         }
 
@@ -115,6 +135,7 @@ impl Mapper for Mapper001 {
                 }
                 0x2000 => {
                     // CHR bank 0
+                    self.chr_bank_0_register = self.load_register;
                     if (self.control_register & CHR_MODE_MASK) != 0 {
                         self.chr_bank_selector_4_lo = self.load_register & 0x1F;
                     } else {
@@ -179,4 +200,8 @@ impl Mapper for Mapper001 {
     fn get_sram(&self) -> Option<&[u8]> {
         Some(&self.ram_data)
     }
+
+    fn get_sram_mut(&mut self) -> Option<&mut [u8]> {
+        Some(&mut self.ram_data)
+    }
 }
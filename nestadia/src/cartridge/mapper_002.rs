@@ -47,4 +47,8 @@ impl Mapper for Mapper002 {
     fn get_sram(&self) -> Option<&[u8]> {
         None
     }
+
+    fn get_sram_mut(&mut self) -> Option<&mut [u8]> {
+        None
+    }
 }
@@ -21,7 +21,7 @@ pub struct Mapper004 {
 }
 
 impl Mapper004 {
-    pub fn new(prg_banks: u8, mirroring: Mirroring) -> Self {
+    pub fn new(prg_banks: u8, prg_ram_size: usize, mirroring: Mirroring) -> Self {
         Self {
             prg_banks,
             prg_bank_selector: [0u8, 0u8, 0u8, prg_banks * 2 - 1],
@@ -31,7 +31,7 @@ impl Mapper004 {
             chr_inverson: false,
             register: [0u8; 8],
             target_register: 0,
-            ram_data: vec![0u8; 0x2000],
+            ram_data: vec![0u8; prg_ram_size],
 
             irq_active: false,
             irq_enabled: false,
@@ -208,7 +208,7 @@ impl Mapper for Mapper004 {
         self.irq_active = false;
     }
 
-    fn irq_scanline(&mut self) {
+    fn irq_scanline(&mut self, _scanline: i16) {
         if self.irq_counter == 0 {
             self.irq_counter = self.irq_reload;
         } else {
@@ -223,4 +223,8 @@ impl Mapper for Mapper004 {
     fn get_sram(&self) -> Option<&[u8]> {
         Some(&self.ram_data)
     }
+
+    fn get_sram_mut(&mut self) -> Option<&mut [u8]> {
+        Some(&mut self.ram_data)
+    }
 }
@@ -0,0 +1,391 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::{CartridgeReadTarget, Mapper, Mirroring, NametableSource, PrgBanks8K};
+
+const EXRAM_SIZE: usize = 0x0400;
+
+// Nintendo MMC5
+// https://wiki.nesdev.com/w/index.php/MMC5
+// Supported: PRG/CHR banking, PRG RAM, ExRAM as nametable or CPU RAM, fill mode,
+// scanline IRQ and multiplier.
+// Not supported yet: extended attributes, vertical split and expansion audio.
+pub struct Mapper005 {
+    prg_banks: PrgBanks8K,
+    prg_mode: u8,
+    prg_bank_selector: [u8; 5], // $5113-$5117
+    prg_ram_protect: [u8; 2],
+    chr_mode: u8,
+    chr_bank_selector_a: [u16; 8], // $5120-$5127, sprites
+    chr_bank_selector_b: [u16; 4], // $5128-$512B, background
+    chr_last_written_b: bool,
+    chr_upper_bits: u16,
+    exram_mode: u8,
+    exram: [u8; EXRAM_SIZE],
+    nametable_mapping: u8,
+    fill_tile: u8,
+    fill_color: u8,
+    multiplicand: u8,
+    multiplier: u8,
+    ram_data: Vec<u8>,
+
+    irq_compare: u8,
+    irq_counter: u8,
+    irq_enabled: bool,
+    irq_pending: bool,
+    irq_active: bool,
+    in_frame: bool,
+}
+
+impl Mapper005 {
+    pub fn new(prg_banks: PrgBanks8K, prg_ram_size: usize) -> Self {
+        Self {
+            prg_banks,
+            prg_mode: 3,
+            prg_bank_selector: [0, 0, 0, 0, 0xFF],
+            prg_ram_protect: [0u8; 2],
+            chr_mode: 0,
+            chr_bank_selector_a: [0u16; 8],
+            chr_bank_selector_b: [0u16; 4],
+            chr_last_written_b: false,
+            chr_upper_bits: 0,
+            exram_mode: 0,
+            exram: [0u8; EXRAM_SIZE],
+            nametable_mapping: 0,
+            fill_tile: 0,
+            fill_color: 0,
+            multiplicand: 0xFF,
+            multiplier: 0xFF,
+            ram_data: vec![0u8; prg_ram_size],
+
+            irq_compare: 0,
+            irq_counter: 0,
+            irq_enabled: false,
+            irq_pending: false,
+            irq_active: false,
+            in_frame: false,
+        }
+    }
+
+    /// Returns whether the 8K bank mapped at `addr` ($8000-$FFFF) is ROM, and its number
+    fn prg_bank(&self, addr: u16) -> (bool, u8) {
+        let slot = ((addr - 0x8000) >> 13) as u8; // 8K slot, 0-3
+        let (selector, bank) = match (self.prg_mode, slot) {
+            (0, _) => (4, (self.prg_bank_selector[4] & 0x7C) | slot),
+            (1, 0..=1) | (2, 0..=1) => (2, (self.prg_bank_selector[2] & 0x7E) | (slot & 0x01)),
+            (1, _) => (4, (self.prg_bank_selector[4] & 0x7E) | (slot & 0x01)),
+            _ => {
+                let selector = slot as usize + 1;
+                (selector, self.prg_bank_selector[selector] & 0x7F)
+            }
+        };
+
+        // $5117 always maps ROM, other registers select ROM with bit 7
+        let rom = selector == 4 || self.prg_bank_selector[selector] & 0x80 == 0x80;
+        (rom, bank)
+    }
+
+    fn prg_ram_addr(&self, bank: u8, addr: u16) -> usize {
+        let bank = (bank as usize & 0x07) % (self.ram_data.len() / 0x2000).max(1);
+        (bank * 0x2000 + (addr & 0x1FFF) as usize) % self.ram_data.len()
+    }
+
+    fn prg_ram_writable(&self) -> bool {
+        self.prg_ram_protect == [0b10, 0b01]
+    }
+
+    fn nametable_slot(&self, addr: u16) -> u8 {
+        let slot = ((addr & 0x0FFF) >> 10) as u8;
+        (self.nametable_mapping >> (slot * 2)) & 0x03
+    }
+}
+
+impl Mapper for Mapper005 {
+    fn cpu_map_read(&self, addr: u16) -> CartridgeReadTarget {
+        match addr {
+            0x5204 => {
+                let mut status = 0;
+                if self.irq_pending {
+                    status |= 0x80;
+                }
+                if self.in_frame {
+                    status |= 0x40;
+                }
+                CartridgeReadTarget::PrgRam(status)
+            }
+            0x5205 => {
+                let product = u16::from(self.multiplicand) * u16::from(self.multiplier);
+                CartridgeReadTarget::PrgRam(product as u8)
+            }
+            0x5206 => {
+                let product = u16::from(self.multiplicand) * u16::from(self.multiplier);
+                CartridgeReadTarget::PrgRam((product >> 8) as u8)
+            }
+            0x5C00..=0x5FFF if self.exram_mode >= 2 => {
+                CartridgeReadTarget::PrgRam(self.exram[(addr & 0x03FF) as usize])
+            }
+            0x6000..=0x7FFF => CartridgeReadTarget::PrgRam(
+                self.ram_data[self.prg_ram_addr(self.prg_bank_selector[0], addr)],
+            ),
+            0x8000..=0xFFFF => {
+                let (rom, bank) = self.prg_bank(addr);
+                if rom {
+                    self.prg_banks.read(usize::from(bank), addr)
+                } else {
+                    CartridgeReadTarget::PrgRam(self.ram_data[self.prg_ram_addr(bank, addr)])
+                }
+            }
+            _ => {
+                // Open bus
+                CartridgeReadTarget::PrgRam(0)
+            }
+        }
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x5100 => self.prg_mode = data & 0x03,
+            0x5101 => self.chr_mode = data & 0x03,
+            0x5102 => self.prg_ram_protect[0] = data & 0x03,
+            0x5103 => self.prg_ram_protect[1] = data & 0x03,
+            0x5104 => self.exram_mode = data & 0x03,
+            0x5105 => self.nametable_mapping = data,
+            0x5106 => self.fill_tile = data,
+            0x5107 => self.fill_color = data & 0x03,
+            0x5113..=0x5117 => self.prg_bank_selector[(addr - 0x5113) as usize] = data,
+            0x5120..=0x5127 => {
+                self.chr_bank_selector_a[(addr - 0x5120) as usize] =
+                    self.chr_upper_bits | u16::from(data);
+                self.chr_last_written_b = false;
+            }
+            0x5128..=0x512B => {
+                self.chr_bank_selector_b[(addr - 0x5128) as usize] =
+                    self.chr_upper_bits | u16::from(data);
+                self.chr_last_written_b = true;
+            }
+            0x5130 => self.chr_upper_bits = u16::from(data & 0x03) << 8,
+            0x5203 => self.irq_compare = data,
+            0x5204 => self.irq_enabled = (data & 0x80) == 0x80,
+            0x5205 => self.multiplicand = data,
+            0x5206 => self.multiplier = data,
+            0x5C00..=0x5FFF => {
+                // Read-only in mode 3. The hardware also drops writes made outside of
+                // rendering in nametable modes, which isn't emulated.
+                if self.exram_mode != 3 {
+                    self.exram[(addr & 0x03FF) as usize] = data;
+                }
+            }
+            0x6000..=0x7FFF => {
+                if self.prg_ram_writable() {
+                    let ram_addr = self.prg_ram_addr(self.prg_bank_selector[0], addr);
+                    self.ram_data[ram_addr] = data;
+                }
+            }
+            0x8000..=0xDFFF => {
+                let (rom, bank) = self.prg_bank(addr);
+                if !rom && self.prg_ram_writable() {
+                    let ram_addr = self.prg_ram_addr(bank, addr);
+                    self.ram_data[ram_addr] = data;
+                }
+            }
+            0xE000..=0xFFFF => (),
+            _ => log::debug!("Ignored write to MMC5 address {:#06x}", addr),
+        }
+    }
+
+    fn ppu_map_read(&self, addr: u16) -> usize {
+        // Sprites and background use different bank sets in 8x16 sprite mode, but the
+        // mapper can't tell which one is fetching: the last written set is used.
+        if self.chr_last_written_b {
+            let regs = &self.chr_bank_selector_b;
+            let bank = match self.chr_mode {
+                0 | 1 => regs[3],
+                2 => regs[(((addr >> 11) & 0x01) * 2 + 1) as usize],
+                _ => regs[((addr >> 10) & 0x03) as usize],
+            } as usize;
+            match self.chr_mode {
+                0 => bank * 0x2000 + (addr & 0x1FFF) as usize,
+                1 => bank * 0x1000 + (addr & 0x0FFF) as usize,
+                2 => bank * 0x0800 + (addr & 0x07FF) as usize,
+                _ => bank * 0x0400 + (addr & 0x03FF) as usize,
+            }
+        } else {
+            let regs = &self.chr_bank_selector_a;
+            match self.chr_mode {
+                0 => regs[7] as usize * 0x2000 + (addr & 0x1FFF) as usize,
+                1 => {
+                    regs[((addr >> 12) * 4 + 3) as usize] as usize * 0x1000
+                        + (addr & 0x0FFF) as usize
+                }
+                2 => {
+                    regs[((addr >> 11) * 2 + 1) as usize] as usize * 0x0800
+                        + (addr & 0x07FF) as usize
+                }
+                _ => regs[(addr >> 10) as usize] as usize * 0x0400 + (addr & 0x03FF) as usize,
+            }
+        }
+    }
+
+    fn ppu_map_write(&self, addr: u16) -> Option<usize> {
+        Some(self.ppu_map_read(addr))
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.nametable_mapping {
+            0x44 => Mirroring::Vertical,
+            0x50 => Mirroring::Horizontal,
+            0x00 => Mirroring::OneScreenLower,
+            0x55 => Mirroring::OneScreenUpper,
+            _ => Mirroring::FourScreen,
+        }
+    }
+
+    fn nametable_source(&self, addr: u16) -> NametableSource {
+        match self.nametable_slot(addr) {
+            0 => NametableSource::Vram(0),
+            1 => NametableSource::Vram(1),
+            _ => NametableSource::Mapper,
+        }
+    }
+
+    fn read_nametable(&self, addr: u16) -> u8 {
+        let offset = (addr & 0x03FF) as usize;
+        match self.nametable_slot(addr) {
+            2 if self.exram_mode <= 1 => self.exram[offset],
+            2 => 0,
+            _ if offset < 0x3C0 => self.fill_tile,
+            _ => self.fill_color * 0x55, // Fill color on all 4 quadrants
+        }
+    }
+
+    fn write_nametable(&mut self, addr: u16, data: u8) {
+        if self.nametable_slot(addr) == 2 && self.exram_mode <= 1 {
+            self.exram[(addr & 0x03FF) as usize] = data;
+        }
+    }
+
+    fn irq_state(&self) -> bool {
+        self.irq_active
+    }
+
+    fn irq_clear(&mut self) {
+        self.irq_active = false;
+    }
+
+    fn cpu_read_side_effects(&mut self, addr: u16) {
+        // Reading $5204 acknowledges the scanline IRQ
+        if addr == 0x5204 {
+            self.irq_pending = false;
+            self.irq_active = false;
+        }
+    }
+
+    fn irq_scanline(&mut self, scanline: i16) {
+        match scanline {
+            0 => {
+                self.in_frame = true;
+                self.irq_counter = 0;
+                self.irq_pending = false;
+            }
+            1..=239 => {
+                self.irq_counter = self.irq_counter.wrapping_add(1);
+                if self.irq_counter == self.irq_compare {
+                    self.irq_pending = true;
+                    if self.irq_enabled {
+                        self.irq_active = true;
+                    }
+                }
+            }
+            _ => self.in_frame = false,
+        }
+    }
+
+    fn get_sram(&self) -> Option<&[u8]> {
+        Some(&self.ram_data)
+    }
+
+    fn get_sram_mut(&mut self) -> Option<&mut [u8]> {
+        Some(&mut self.ram_data)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn prg_banking_modes() {
+        let mut mapper = Mapper005::new(PrgBanks8K(16), 0x2000);
+        mapper.cpu_map_write(0x5100, 3);
+        mapper.cpu_map_write(0x5114, 0x85);
+        assert!(matches!(
+            mapper.cpu_map_read(0x8001),
+            CartridgeReadTarget::PrgRom(0xA001)
+        ));
+        assert!(matches!(
+            mapper.cpu_map_read(0xE000),
+            CartridgeReadTarget::PrgRom(0x1E000)
+        ));
+
+        // 32K mode ignores the 2 low bits of $5117
+        mapper.cpu_map_write(0x5100, 0);
+        mapper.cpu_map_write(0x5117, 0x05);
+        assert!(matches!(
+            mapper.cpu_map_read(0xA000),
+            CartridgeReadTarget::PrgRom(0x0A000)
+        ));
+    }
+
+    #[test]
+    fn exram_and_fill_nametables() {
+        let mut mapper = Mapper005::new(PrgBanks8K(4), 0x2000);
+        mapper.cpu_map_write(0x5105, 0b11_10_01_00);
+        mapper.cpu_map_write(0x5106, 0x42);
+        mapper.cpu_map_write(0x5107, 0x02);
+
+        assert!(matches!(
+            mapper.nametable_source(0x2400),
+            NametableSource::Vram(1)
+        ));
+        mapper.write_nametable(0x2810, 0x99);
+        assert_eq!(mapper.read_nametable(0x2810), 0x99);
+        assert_eq!(mapper.exram[0x10], 0x99);
+        assert_eq!(mapper.read_nametable(0x2C00), 0x42);
+        assert_eq!(mapper.read_nametable(0x2FC0), 0xAA);
+    }
+
+    #[test]
+    fn scanline_irq() {
+        let mut mapper = Mapper005::new(PrgBanks8K(4), 0x2000);
+        mapper.cpu_map_write(0x5203, 10);
+        mapper.cpu_map_write(0x5204, 0x80);
+
+        for scanline in 0..10 {
+            mapper.irq_scanline(scanline);
+        }
+        assert!(!mapper.irq_state());
+        mapper.irq_scanline(10);
+        assert!(mapper.irq_state());
+        assert!(matches!(
+            mapper.cpu_map_read(0x5204),
+            CartridgeReadTarget::PrgRam(0xC0)
+        ));
+    }
+
+    #[test]
+    fn status_read_acknowledges_irq() {
+        let mut mapper = Mapper005::new(PrgBanks8K(4), 0x2000);
+        mapper.cpu_map_write(0x5203, 1);
+        mapper.cpu_map_write(0x5204, 0x80);
+        mapper.irq_scanline(0);
+        mapper.irq_scanline(1);
+        assert!(mapper.irq_state());
+
+        mapper.cpu_read_side_effects(0x5204);
+        assert!(!mapper.irq_state());
+        assert!(matches!(
+            mapper.cpu_map_read(0x5204),
+            CartridgeReadTarget::PrgRam(0x40)
+        ));
+    }
+}
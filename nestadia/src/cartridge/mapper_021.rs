@@ -0,0 +1,282 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::{CartridgeReadTarget, Mapper, Mirroring, PrgBanks8K};
+
+const IRQ_PRESCALER_RELOAD: i16 = 341;
+
+// Konami VRC2 / VRC4, shared by mappers 21, 22, 23 and 25
+// https://wiki.nesdev.com/w/index.php/VRC2_and_VRC4
+pub struct Mapper021 {
+    mapper_id: u8,
+    vrc4: bool,
+    prg_banks: PrgBanks8K,
+    prg_bank_selector: [u8; 2],
+    prg_swap_mode: bool,
+    chr_bank_selector: [u16; 8],
+    mirroring: Mirroring,
+    ram_data: Vec<u8>,
+
+    irq_latch: u8,
+    irq_counter: u8,
+    irq_prescaler: i16,
+    irq_enabled: bool,
+    irq_enabled_after_ack: bool,
+    irq_cycle_mode: bool,
+    irq_active: bool,
+}
+
+impl Mapper021 {
+    pub fn new(
+        mapper_id: u8,
+        prg_banks: PrgBanks8K,
+        prg_ram_size: usize,
+        mirroring: Mirroring,
+    ) -> Self {
+        Self {
+            mapper_id,
+            vrc4: mapper_id == 21,
+            prg_banks,
+            prg_bank_selector: [0u8; 2],
+            prg_swap_mode: false,
+            chr_bank_selector: [0u16; 8],
+            mirroring,
+            ram_data: vec![0u8; prg_ram_size],
+
+            irq_latch: 0,
+            irq_counter: 0,
+            irq_prescaler: IRQ_PRESCALER_RELOAD,
+            irq_enabled: false,
+            irq_enabled_after_ack: false,
+            irq_cycle_mode: false,
+            irq_active: false,
+        }
+    }
+
+    /// Boards wire different CPU address lines to the two register select inputs.
+    /// Returns the register index (0-3) within the $x000 range.
+    fn register_index(&self, addr: u16) -> u16 {
+        let line = |n: u16| (addr >> n) & 0x01;
+        let (lo, hi) = match self.mapper_id {
+            // VRC4a (A1, A2) and VRC4c (A6, A7)
+            21 => (line(1) | line(6), line(2) | line(7)),
+            // VRC2a (A1, A0)
+            22 => (line(1), line(0)),
+            // VRC4f and VRC2b (A0, A1), VRC4e (A2, A3)
+            23 => (line(0) | line(2), line(1) | line(3)),
+            // VRC4b and VRC2c (A1, A0), VRC4d (A3, A2)
+            25 => (line(1) | line(3), line(0) | line(2)),
+            _ => unreachable!(),
+        };
+        (hi << 1) | lo
+    }
+
+    fn clock_irq_counter(&mut self) {
+        if self.irq_counter == 0xFF {
+            self.irq_counter = self.irq_latch;
+            self.irq_active = true;
+        } else {
+            self.irq_counter += 1;
+        }
+    }
+}
+
+impl Mapper for Mapper021 {
+    fn cpu_map_read(&self, addr: u16) -> CartridgeReadTarget {
+        let second_last_bank = self.prg_banks.second_last();
+        match addr {
+            0x6000..=0x7FFF => CartridgeReadTarget::PrgRam(self.ram_data[(addr & 0x1FFF) as usize]),
+            0x8000..=0x9FFF if self.prg_swap_mode => self.prg_banks.read(second_last_bank, addr),
+            0x8000..=0x9FFF => self
+                .prg_banks
+                .read(usize::from(self.prg_bank_selector[0]), addr),
+            0xA000..=0xBFFF => self
+                .prg_banks
+                .read(usize::from(self.prg_bank_selector[1]), addr),
+            0xC000..=0xDFFF if self.prg_swap_mode => self
+                .prg_banks
+                .read(usize::from(self.prg_bank_selector[0]), addr),
+            0xC000..=0xDFFF => self.prg_banks.read(second_last_bank, addr),
+            0xE000..=0xFFFF => self.prg_banks.read(self.prg_banks.last(), addr),
+            _ => {
+                log::warn!("Attempted to read address w/o known mapping {:#06x}", addr);
+                CartridgeReadTarget::PrgRom(0)
+            }
+        }
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, data: u8) {
+        if let 0x6000..=0x7FFF = addr {
+            self.ram_data[(addr & 0x1FFF) as usize] = data;
+            return;
+        }
+
+        // Mappers 23 and 25 are either VRC2 or VRC4. Only VRC4 has the IRQ registers
+        // and boards wired to the A2/A3 address lines, tell them apart on first use.
+        if self.mapper_id != 22 && (addr & 0xF000 == 0xF000 || addr & 0x000C != 0) {
+            self.vrc4 = true;
+        }
+
+        let register = self.register_index(addr);
+        match (addr & 0xF000, register) {
+            (0x8000, _) => self.prg_bank_selector[0] = data & 0x1F,
+            // VRC2 has no PRG swap mode, all four registers are the mirroring
+            (0x9000, register) if register < 2 || !self.vrc4 => {
+                // VRC2 only has the low bit
+                let mask = if self.mapper_id == 22 { 0x01 } else { 0x03 };
+                self.mirroring = match data & mask {
+                    0 => Mirroring::Vertical,
+                    1 => Mirroring::Horizontal,
+                    2 => Mirroring::OneScreenLower,
+                    3 => Mirroring::OneScreenUpper,
+                    _ => unreachable!(),
+                }
+            }
+            (0x9000, _) => self.prg_swap_mode = (data & 0x02) == 0x02,
+            (0xA000, _) => self.prg_bank_selector[1] = data & 0x1F,
+            (0xB000..=0xE000, _) => {
+                // Each 1K CHR bank number is written in two halves
+                let bank = (((addr & 0xF000) - 0xB000) >> 11) as usize | (register >> 1) as usize;
+                let selector = &mut self.chr_bank_selector[bank];
+                if register & 0x01 == 0 {
+                    *selector = (*selector & 0x1F0) | u16::from(data & 0x0F);
+                } else {
+                    *selector = (*selector & 0x00F) | (u16::from(data & 0x1F) << 4);
+                }
+            }
+            (0xF000, 0) => self.irq_latch = (self.irq_latch & 0xF0) | (data & 0x0F),
+            (0xF000, 1) => self.irq_latch = (self.irq_latch & 0x0F) | ((data & 0x0F) << 4),
+            (0xF000, 2) => {
+                // IRQ control
+                self.irq_enabled_after_ack = (data & 0x01) == 0x01;
+                self.irq_enabled = (data & 0x02) == 0x02;
+                self.irq_cycle_mode = (data & 0x04) == 0x04;
+                self.irq_active = false;
+                if self.irq_enabled {
+                    self.irq_counter = self.irq_latch;
+                    self.irq_prescaler = IRQ_PRESCALER_RELOAD;
+                }
+            }
+            (0xF000, _) => {
+                // IRQ acknowledge
+                self.irq_active = false;
+                self.irq_enabled = self.irq_enabled_after_ack;
+            }
+            _ => log::warn!(
+                "Attempted to write to address w/o known mapping: {:#06x}",
+                addr
+            ),
+        }
+    }
+
+    fn ppu_map_read(&self, addr: u16) -> usize {
+        let mut bank = self.chr_bank_selector[((addr & 0x1FFF) >> 10) as usize] as usize;
+        if self.mapper_id == 22 {
+            // VRC2a ignores the low bit of CHR bank numbers
+            bank >>= 1;
+        }
+        bank * 0x0400 + (addr & 0x03FF) as usize
+    }
+
+    fn ppu_map_write(&self, addr: u16) -> Option<usize> {
+        Some(self.ppu_map_read(addr))
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn irq_state(&self) -> bool {
+        self.irq_active
+    }
+
+    fn irq_clear(&mut self) {
+        self.irq_active = false;
+    }
+
+    fn cpu_clock(&mut self) {
+        if !self.irq_enabled {
+            return;
+        }
+
+        if self.irq_cycle_mode {
+            self.clock_irq_counter();
+        } else {
+            // Scanline mode: the prescaler divides CPU cycles by 113.667
+            self.irq_prescaler -= 3;
+            if self.irq_prescaler <= 0 {
+                self.irq_prescaler += IRQ_PRESCALER_RELOAD;
+                self.clock_irq_counter();
+            }
+        }
+    }
+
+    fn get_sram(&self) -> Option<&[u8]> {
+        Some(&self.ram_data)
+    }
+
+    fn get_sram_mut(&mut self) -> Option<&mut [u8]> {
+        Some(&mut self.ram_data)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn irq_prescaler_clocks_counter_every_scanline() {
+        let mut mapper = Mapper021::new(21, PrgBanks8K(4), 0x2000, Mirroring::Vertical);
+        mapper.cpu_map_write(0xF000, 0x0E); // Latch low
+        mapper.cpu_map_write(0xF002, 0x0F); // Latch high, $FE
+        mapper.cpu_map_write(0xF004, 0x02); // Enable, scanline mode
+
+        // 113 CPU cycles don't make a full scanline yet
+        (0..113).for_each(|_| mapper.cpu_clock());
+        assert_eq!(mapper.irq_counter, 0xFE);
+
+        mapper.cpu_clock();
+        assert_eq!(mapper.irq_counter, 0xFF);
+        assert!(!mapper.irq_state());
+
+        // The counter overflows on the next scanline
+        (0..114).for_each(|_| mapper.cpu_clock());
+        assert!(mapper.irq_state());
+        assert_eq!(mapper.irq_counter, 0xFE);
+    }
+
+    #[test]
+    fn prg_swap_mode_vrc4_only() {
+        // VRC2a mirrors the mirroring register at $9002
+        let mut vrc2a = Mapper021::new(22, PrgBanks8K(4), 0x2000, Mirroring::Vertical);
+        vrc2a.cpu_map_write(0x9002, 0x03);
+        assert!(!vrc2a.prg_swap_mode);
+        assert!(matches!(vrc2a.mirroring(), Mirroring::Horizontal));
+
+        let mut vrc4a = Mapper021::new(21, PrgBanks8K(4), 0x2000, Mirroring::Vertical);
+        vrc4a.cpu_map_write(0x9004, 0x02);
+        assert!(vrc4a.prg_swap_mode);
+        assert!(matches!(
+            vrc4a.cpu_map_read(0x8000),
+            CartridgeReadTarget::PrgRom(0x4000)
+        ));
+
+        // Mapper 23 is VRC2b until the game uses VRC4 registers
+        let mut mapper = Mapper021::new(23, PrgBanks8K(4), 0x2000, Mirroring::Vertical);
+        mapper.cpu_map_write(0x9002, 0x02);
+        assert!(!mapper.prg_swap_mode);
+        mapper.cpu_map_write(0xF003, 0x00);
+        mapper.cpu_map_write(0x9002, 0x02);
+        assert!(mapper.prg_swap_mode);
+    }
+
+    #[test]
+    fn register_address_lines() {
+        let vrc4a = Mapper021::new(21, PrgBanks8K(4), 0x2000, Mirroring::Vertical);
+        assert_eq!(vrc4a.register_index(0x9004), 2);
+        assert_eq!(vrc4a.register_index(0x9080), 2);
+        let vrc4b = Mapper021::new(25, PrgBanks8K(4), 0x2000, Mirroring::Vertical);
+        assert_eq!(vrc4b.register_index(0x9001), 2);
+        assert_eq!(vrc4b.register_index(0x9008), 1);
+    }
+}
@@ -0,0 +1,253 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::{CartridgeReadTarget, Mapper, Mirroring, PrgBanks8K};
+
+// Sunsoft FME-7
+// https://wiki.nesdev.com/w/index.php/Sunsoft_FME-7
+pub struct Mapper069 {
+    prg_banks: PrgBanks8K,
+    command: u8,
+    prg_bank_selector: [u8; 4],
+    chr_bank_selector: [u8; 8],
+    prg_ram_selected: bool,
+    prg_ram_enabled: bool,
+    mirroring: Mirroring,
+    ram_data: Vec<u8>,
+
+    irq_enabled: bool,
+    irq_counter_enabled: bool,
+    irq_active: bool,
+    irq_counter: u16,
+}
+
+impl Mapper069 {
+    pub fn new(prg_banks: PrgBanks8K, prg_ram_size: usize, mirroring: Mirroring) -> Self {
+        Self {
+            prg_banks,
+            command: 0,
+            prg_bank_selector: [0u8; 4],
+            chr_bank_selector: [0u8; 8],
+            prg_ram_selected: false,
+            prg_ram_enabled: false,
+            mirroring,
+            ram_data: vec![0u8; prg_ram_size],
+
+            irq_enabled: false,
+            irq_counter_enabled: false,
+            irq_active: false,
+            irq_counter: 0,
+        }
+    }
+}
+
+impl Mapper for Mapper069 {
+    fn cpu_map_read(&self, addr: u16) -> CartridgeReadTarget {
+        match addr {
+            0x6000..=0x7FFF => {
+                if !self.prg_ram_selected {
+                    self.prg_banks
+                        .read(usize::from(self.prg_bank_selector[0]), addr)
+                } else if self.prg_ram_enabled {
+                    CartridgeReadTarget::PrgRam(self.ram_data[(addr & 0x1FFF) as usize])
+                } else {
+                    // Open bus
+                    CartridgeReadTarget::PrgRam(0)
+                }
+            }
+            0x8000..=0x9FFF => self
+                .prg_banks
+                .read(usize::from(self.prg_bank_selector[1]), addr),
+            0xA000..=0xBFFF => self
+                .prg_banks
+                .read(usize::from(self.prg_bank_selector[2]), addr),
+            0xC000..=0xDFFF => self
+                .prg_banks
+                .read(usize::from(self.prg_bank_selector[3]), addr),
+            0xE000..=0xFFFF => self.prg_banks.read(self.prg_banks.last(), addr),
+            _ => {
+                log::warn!("Attempted to read address w/o known mapping {:#06x}", addr);
+                CartridgeReadTarget::PrgRom(0)
+            }
+        }
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x6000..=0x7FFF => {
+                if self.prg_ram_selected && self.prg_ram_enabled {
+                    self.ram_data[(addr & 0x1FFF) as usize] = data;
+                }
+            }
+            0x8000..=0x9FFF => {
+                // Command register
+                self.command = data & 0x0F;
+            }
+            0xA000..=0xBFFF => {
+                // Parameter register
+                match self.command {
+                    0x0..=0x7 => self.chr_bank_selector[self.command as usize] = data,
+                    0x8 => {
+                        self.prg_ram_enabled = (data & 0x80) == 0x80;
+                        self.prg_ram_selected = (data & 0x40) == 0x40;
+                        self.prg_bank_selector[0] = data & 0x3F;
+                    }
+                    0x9..=0xB => {
+                        self.prg_bank_selector[(self.command - 0x8) as usize] = data & 0x3F
+                    }
+                    0xC => {
+                        self.mirroring = match data & 0x03 {
+                            0 => Mirroring::Vertical,
+                            1 => Mirroring::Horizontal,
+                            2 => Mirroring::OneScreenLower,
+                            3 => Mirroring::OneScreenUpper,
+                            _ => unreachable!(),
+                        }
+                    }
+                    0xD => {
+                        // IRQ control, also acknowledges any pending IRQ
+                        self.irq_enabled = (data & 0x01) == 0x01;
+                        self.irq_counter_enabled = (data & 0x80) == 0x80;
+                        self.irq_active = false;
+                    }
+                    0xE => self.irq_counter = (self.irq_counter & 0xFF00) | u16::from(data),
+                    0xF => self.irq_counter = (self.irq_counter & 0x00FF) | (u16::from(data) << 8),
+                    _ => unreachable!(),
+                }
+            }
+            0xC000..=0xFFFF => {
+                // Sunsoft 5B audio registers
+                // Expansion audio is not emulated
+            }
+            _ => log::warn!(
+                "Attempted to write to address w/o known mapping: {:#06x}",
+                addr
+            ),
+        }
+    }
+
+    fn ppu_map_read(&self, addr: u16) -> usize {
+        let bank = self.chr_bank_selector[((addr & 0x1FFF) >> 10) as usize];
+        (bank as usize) * 0x0400 + (addr & 0x03FF) as usize
+    }
+
+    fn ppu_map_write(&self, addr: u16) -> Option<usize> {
+        Some(self.ppu_map_read(addr))
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn irq_state(&self) -> bool {
+        self.irq_active
+    }
+
+    fn irq_clear(&mut self) {
+        self.irq_active = false;
+    }
+
+    fn cpu_clock(&mut self) {
+        if !self.irq_counter_enabled {
+            return;
+        }
+
+        // The IRQ fires when the counter wraps from $0000 to $FFFF
+        self.irq_counter = self.irq_counter.wrapping_sub(1);
+        if self.irq_counter == 0xFFFF && self.irq_enabled {
+            self.irq_active = true;
+        }
+    }
+
+    fn get_sram(&self) -> Option<&[u8]> {
+        Some(&self.ram_data)
+    }
+
+    fn get_sram_mut(&mut self) -> Option<&mut [u8]> {
+        Some(&mut self.ram_data)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn command_and_parameter() {
+        let mut mapper = Mapper069::new(PrgBanks8K(16), 0x2000, Mirroring::Vertical);
+        mapper.cpu_map_write(0x8000, 0x03);
+        mapper.cpu_map_write(0xA000, 0x12);
+        assert_eq!(mapper.ppu_map_read(0x0C10), 0x12 * 0x0400 + 0x10);
+
+        // The command stays selected for the next parameter writes
+        mapper.cpu_map_write(0xBFFF, 0x13);
+        assert_eq!(mapper.chr_bank_selector[3], 0x13);
+
+        mapper.cpu_map_write(0x9FFF, 0x09);
+        mapper.cpu_map_write(0xA000, 0x05);
+        assert!(matches!(
+            mapper.cpu_map_read(0x8001),
+            CartridgeReadTarget::PrgRom(0xA001)
+        ));
+
+        mapper.cpu_map_write(0x8000, 0x0C);
+        mapper.cpu_map_write(0xA000, 0x03);
+        assert!(matches!(mapper.mirroring(), Mirroring::OneScreenUpper));
+    }
+
+    #[test]
+    fn prg_ram_select() {
+        let mut mapper = Mapper069::new(PrgBanks8K(16), 0x2000, Mirroring::Vertical);
+        mapper.cpu_map_write(0x8000, 0x08);
+
+        // ROM bank 2 at $6000
+        mapper.cpu_map_write(0xA000, 0x02);
+        assert!(matches!(
+            mapper.cpu_map_read(0x6010),
+            CartridgeReadTarget::PrgRom(0x4010)
+        ));
+
+        // RAM selected but disabled, writes are ignored
+        mapper.cpu_map_write(0xA000, 0x40);
+        mapper.cpu_map_write(0x6010, 0x55);
+        assert!(matches!(
+            mapper.cpu_map_read(0x6010),
+            CartridgeReadTarget::PrgRam(0)
+        ));
+
+        mapper.cpu_map_write(0xA000, 0xC0);
+        mapper.cpu_map_write(0x6010, 0x55);
+        assert!(matches!(
+            mapper.cpu_map_read(0x6010),
+            CartridgeReadTarget::PrgRam(0x55)
+        ));
+    }
+
+    #[test]
+    fn irq_counter_wraps() {
+        let mut mapper = Mapper069::new(PrgBanks8K(16), 0x2000, Mirroring::Vertical);
+        mapper.cpu_map_write(0x8000, 0x0E);
+        mapper.cpu_map_write(0xA000, 0x02);
+        mapper.cpu_map_write(0x8000, 0x0F);
+        mapper.cpu_map_write(0xA000, 0x00);
+
+        // The counter doesn't run until enabled
+        mapper.cpu_clock();
+        assert_eq!(mapper.irq_counter, 0x0002);
+
+        mapper.cpu_map_write(0x8000, 0x0D);
+        mapper.cpu_map_write(0xA000, 0x81);
+        (0..2).for_each(|_| mapper.cpu_clock());
+        assert!(!mapper.irq_state());
+        mapper.cpu_clock();
+        assert_eq!(mapper.irq_counter, 0xFFFF);
+        assert!(mapper.irq_state());
+
+        // Writing the IRQ control acknowledges it, the counter keeps running
+        mapper.cpu_map_write(0xA000, 0x81);
+        assert!(!mapper.irq_state());
+        mapper.cpu_clock();
+        assert_eq!(mapper.irq_counter, 0xFFFE);
+        assert!(!mapper.irq_state());
+    }
+}
@@ -0,0 +1,240 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::{CartridgeReadTarget, Mapper, Mirroring, PrgBanks8K};
+
+const IRQ_PRESCALER_RELOAD: i16 = 341;
+
+// Konami VRC7
+// https://wiki.nesdev.com/w/index.php/VRC7
+pub struct Mapper085 {
+    prg_banks: PrgBanks8K,
+    prg_bank_selector: [u8; 3],
+    chr_bank_selector: [u8; 8],
+    mirroring: Mirroring,
+    ram_enabled: bool,
+    ram_data: Vec<u8>,
+
+    irq_latch: u8,
+    irq_counter: u8,
+    irq_prescaler: i16,
+    irq_enabled: bool,
+    irq_enabled_after_ack: bool,
+    irq_cycle_mode: bool,
+    irq_active: bool,
+}
+
+impl Mapper085 {
+    pub fn new(prg_banks: PrgBanks8K, prg_ram_size: usize, mirroring: Mirroring) -> Self {
+        Self {
+            prg_banks,
+            prg_bank_selector: [0u8; 3],
+            chr_bank_selector: [0u8; 8],
+            mirroring,
+            ram_enabled: false,
+            ram_data: vec![0u8; prg_ram_size],
+
+            irq_latch: 0,
+            irq_counter: 0,
+            irq_prescaler: IRQ_PRESCALER_RELOAD,
+            irq_enabled: false,
+            irq_enabled_after_ack: false,
+            irq_cycle_mode: false,
+            irq_active: false,
+        }
+    }
+
+    fn clock_irq_counter(&mut self) {
+        if self.irq_counter == 0xFF {
+            self.irq_counter = self.irq_latch;
+            self.irq_active = true;
+        } else {
+            self.irq_counter += 1;
+        }
+    }
+}
+
+impl Mapper for Mapper085 {
+    fn cpu_map_read(&self, addr: u16) -> CartridgeReadTarget {
+        match addr {
+            0x6000..=0x7FFF if self.ram_enabled => {
+                CartridgeReadTarget::PrgRam(self.ram_data[(addr & 0x1FFF) as usize])
+            }
+            // Open bus
+            0x6000..=0x7FFF => CartridgeReadTarget::PrgRam(0),
+            0x8000..=0x9FFF => self
+                .prg_banks
+                .read(usize::from(self.prg_bank_selector[0]), addr),
+            0xA000..=0xBFFF => self
+                .prg_banks
+                .read(usize::from(self.prg_bank_selector[1]), addr),
+            0xC000..=0xDFFF => self
+                .prg_banks
+                .read(usize::from(self.prg_bank_selector[2]), addr),
+            0xE000..=0xFFFF => self.prg_banks.read(self.prg_banks.last(), addr),
+            _ => {
+                log::warn!("Attempted to read address w/o known mapping {:#06x}", addr);
+                CartridgeReadTarget::PrgRom(0)
+            }
+        }
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, data: u8) {
+        if let 0x6000..=0x7FFF = addr {
+            if self.ram_enabled {
+                self.ram_data[(addr & 0x1FFF) as usize] = data;
+            }
+            return;
+        }
+
+        // VRC7a selects the second register of each range with A4, VRC7b with A3
+        let second = addr & 0x0018 != 0;
+        match (addr & 0xF000, second) {
+            (0x8000, false) => self.prg_bank_selector[0] = data & 0x3F,
+            (0x8000, true) => self.prg_bank_selector[1] = data & 0x3F,
+            (0x9000, false) => self.prg_bank_selector[2] = data & 0x3F,
+            (0x9000, true) => {
+                // Audio register select and data ($9010, $9030)
+                // Expansion audio is not emulated
+            }
+            (0xA000..=0xD000, _) => {
+                let bank = (((addr & 0xF000) - 0xA000) >> 11) as usize | second as usize;
+                self.chr_bank_selector[bank] = data;
+            }
+            (0xE000, false) => {
+                self.mirroring = match data & 0x03 {
+                    0 => Mirroring::Vertical,
+                    1 => Mirroring::Horizontal,
+                    2 => Mirroring::OneScreenLower,
+                    3 => Mirroring::OneScreenUpper,
+                    _ => unreachable!(),
+                };
+                self.ram_enabled = (data & 0x80) == 0x80;
+            }
+            (0xE000, true) => self.irq_latch = data,
+            (0xF000, false) => {
+                // IRQ control
+                self.irq_enabled_after_ack = (data & 0x01) == 0x01;
+                self.irq_enabled = (data & 0x02) == 0x02;
+                self.irq_cycle_mode = (data & 0x04) == 0x04;
+                self.irq_active = false;
+                if self.irq_enabled {
+                    self.irq_counter = self.irq_latch;
+                    self.irq_prescaler = IRQ_PRESCALER_RELOAD;
+                }
+            }
+            (0xF000, true) => {
+                // IRQ acknowledge
+                self.irq_active = false;
+                self.irq_enabled = self.irq_enabled_after_ack;
+            }
+            _ => log::warn!(
+                "Attempted to write to address w/o known mapping: {:#06x}",
+                addr
+            ),
+        }
+    }
+
+    fn ppu_map_read(&self, addr: u16) -> usize {
+        let bank = self.chr_bank_selector[((addr & 0x1FFF) >> 10) as usize];
+        (bank as usize) * 0x0400 + (addr & 0x03FF) as usize
+    }
+
+    fn ppu_map_write(&self, addr: u16) -> Option<usize> {
+        Some(self.ppu_map_read(addr))
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn irq_state(&self) -> bool {
+        self.irq_active
+    }
+
+    fn irq_clear(&mut self) {
+        self.irq_active = false;
+    }
+
+    fn cpu_clock(&mut self) {
+        if !self.irq_enabled {
+            return;
+        }
+
+        if self.irq_cycle_mode {
+            self.clock_irq_counter();
+        } else {
+            // Scanline mode: the prescaler divides CPU cycles by 113.667
+            self.irq_prescaler -= 3;
+            if self.irq_prescaler <= 0 {
+                self.irq_prescaler += IRQ_PRESCALER_RELOAD;
+                self.clock_irq_counter();
+            }
+        }
+    }
+
+    fn get_sram(&self) -> Option<&[u8]> {
+        Some(&self.ram_data)
+    }
+
+    fn get_sram_mut(&mut self) -> Option<&mut [u8]> {
+        Some(&mut self.ram_data)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn register_address_lines() {
+        let mut mapper = Mapper085::new(PrgBanks8K(16), 0x2000, Mirroring::Vertical);
+        mapper.cpu_map_write(0x8000, 0x01);
+        mapper.cpu_map_write(0x8010, 0x02); // VRC7a
+        mapper.cpu_map_write(0x9000, 0x03);
+        assert_eq!(mapper.prg_bank_selector, [1, 2, 3]);
+        mapper.cpu_map_write(0x8008, 0x04); // VRC7b
+        assert_eq!(mapper.prg_bank_selector, [1, 4, 3]);
+
+        mapper.cpu_map_write(0xA000, 0x10);
+        mapper.cpu_map_write(0xA010, 0x11);
+        mapper.cpu_map_write(0xD008, 0x17);
+        assert_eq!(mapper.ppu_map_read(0x0000), 0x10 * 0x0400);
+        assert_eq!(mapper.ppu_map_read(0x0400), 0x11 * 0x0400);
+        assert_eq!(mapper.ppu_map_read(0x1FFF), 0x17 * 0x0400 + 0x03FF);
+    }
+
+    #[test]
+    fn prg_ram_enable() {
+        let mut mapper = Mapper085::new(PrgBanks8K(16), 0x2000, Mirroring::Vertical);
+        mapper.cpu_map_write(0x6000, 0x55);
+        mapper.cpu_map_write(0xE000, 0x81);
+        assert!(matches!(mapper.mirroring(), Mirroring::Horizontal));
+        assert!(matches!(
+            mapper.cpu_map_read(0x6000),
+            CartridgeReadTarget::PrgRam(0)
+        ));
+
+        mapper.cpu_map_write(0x6000, 0x55);
+        assert!(matches!(
+            mapper.cpu_map_read(0x6000),
+            CartridgeReadTarget::PrgRam(0x55)
+        ));
+    }
+
+    #[test]
+    fn irq_cycle_mode() {
+        let mut mapper = Mapper085::new(PrgBanks8K(16), 0x2000, Mirroring::Vertical);
+        mapper.cpu_map_write(0xE010, 0xFD);
+        mapper.cpu_map_write(0xF000, 0x06); // Enable, cycle mode
+
+        (0..2).for_each(|_| mapper.cpu_clock());
+        assert!(!mapper.irq_state());
+        mapper.cpu_clock();
+        assert!(mapper.irq_state());
+
+        mapper.cpu_map_write(0xF010, 0x00);
+        assert!(!mapper.irq_state());
+        assert!(!mapper.irq_enabled);
+    }
+}
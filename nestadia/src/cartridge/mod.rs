@@ -4,22 +4,44 @@ mod mapper_001;
 mod mapper_002;
 mod mapper_003;
 mod mapper_004;
+mod mapper_005;
+mod mapper_021;
 mod mapper_066;
+mod mapper_069;
+mod mapper_085;
+mod unif;
 
 use alloc::boxed::Box;
 use alloc::vec;
 use alloc::vec::Vec;
 use core::convert::TryFrom as _;
 
-use self::ines_header::{Flags6, INesHeader};
+use self::ines_header::{Flags6, Flags9, INesHeader};
 use self::mapper_000::Mapper000;
 use self::mapper_002::Mapper002;
 use self::mapper_003::Mapper003;
 use self::mapper_004::Mapper004;
+use self::mapper_005::Mapper005;
+use self::mapper_021::Mapper021;
 use self::mapper_066::Mapper066;
+use self::mapper_069::Mapper069;
+use self::mapper_085::Mapper085;
+use self::unif::UnifRom;
 use crate::cartridge::mapper_001::Mapper001;
 
-#[derive(Debug, Clone, Copy)]
+const PRG_BANK_SIZE: usize = 16384;
+const CHR_BANK_SIZE: usize = 8192;
+const PRG_RAM_WINDOW_SIZE: usize = 8192;
+const PRG_8K_BANK_SIZE: usize = 0x2000;
+
+/// Forced PRG banks are 8K, in four slots covering $8000-$FFFF
+#[cfg(feature = "debugger")]
+const FORCED_PRG_BANK_SIZE: usize = 0x2000;
+/// Forced CHR banks are 1K, in eight slots covering $0000-$1FFF
+#[cfg(feature = "debugger")]
+const FORCED_CHR_BANK_SIZE: usize = 0x0400;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Mirroring {
     Horizontal,
     Vertical,
@@ -28,11 +50,87 @@ pub enum Mirroring {
     OneScreenUpper,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    Ntsc,
+    Pal,
+}
+
+/// File formats that can be recognized from their magic bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RomFormat {
+    INes,
+    Nes2,
+    /// Famicom Disk System image, with or without the fwNES header
+    Fds,
+    Nsf,
+    Unif,
+}
+
+impl RomFormat {
+    /// Whether `Cartridge::load` can parse this format
+    pub fn is_supported(&self) -> bool {
+        matches!(self, RomFormat::INes | RomFormat::Nes2 | RomFormat::Unif)
+    }
+}
+
+/// Guesses the format of a ROM file from its first bytes
+pub fn detect_format(data: &[u8]) -> Option<RomFormat> {
+    if data.starts_with(b"NES\x1A") {
+        // NES 2.0 is identified by bits 2-3 of flags 7 being 0b10
+        match data.get(7) {
+            Some(flags7) if flags7 & 0x0C == 0x08 => Some(RomFormat::Nes2),
+            _ => Some(RomFormat::INes),
+        }
+    } else if data.starts_with(b"NESM\x1A") {
+        Some(RomFormat::Nsf)
+    } else if data.starts_with(b"FDS\x1A") || data.starts_with(b"\x01*NINTENDO-HVC*") {
+        Some(RomFormat::Fds)
+    } else if data.starts_with(b"UNIF") {
+        Some(RomFormat::Unif)
+    } else {
+        None
+    }
+}
+
+/// ROM information, as declared by the header
+#[derive(Debug, Clone, Copy)]
+pub struct CartridgeInfo {
+    pub mapper: u8,
+    /// PRG ROM size in bytes
+    pub prg_rom_size: usize,
+    /// CHR ROM size in bytes, 0 if the cartridge uses CHR RAM
+    pub chr_rom_size: usize,
+    /// CHR RAM size in bytes, 0 if the cartridge uses CHR ROM
+    pub chr_ram_size: usize,
+    pub mirroring: Mirroring,
+    /// PRG RAM size in bytes, as allocated for the mapper
+    pub prg_ram_size: usize,
+    /// Whether the cartridge has battery-backed PRG RAM
+    pub battery: bool,
+    pub region: Region,
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum RomParserError {
     TooShort,
     InvalidMagicBytes,
     MapperNotImplemented,
+    /// The UNIF board name doesn't match any implemented mapper
+    UnknownBoard,
+    /// The file holds less PRG ROM than declared by the header (sizes in bytes)
+    PrgSizeMismatch {
+        expected: usize,
+        actual: usize,
+    },
+    /// The file holds less CHR ROM than declared by the header (sizes in bytes)
+    ChrSizeMismatch {
+        expected: usize,
+        actual: usize,
+    },
+    /// The ROM can't replace the loaded one in place because its mapper or
+    /// memory sizes differ, the emulator must be created again
+    IncompatibleReload,
 }
 
 impl core::fmt::Display for RomParserError {
@@ -41,24 +139,78 @@ impl core::fmt::Display for RomParserError {
     }
 }
 
+/// Where a nametable access ($2000-$2FFF) is routed to
+pub enum NametableSource {
+    /// Console VRAM, following the cartridge mirroring
+    Mirroring,
+    /// 1K page of console VRAM
+    Vram(usize),
+    /// Memory or logic on the cartridge
+    Mapper,
+}
+
 enum CartridgeReadTarget {
     PrgRam(u8),
     PrgRom(usize),
 }
 
+/// Number of 8K banks in PRG ROM, for mappers switching PRG ROM by 8K.
+/// PRG ROM is never empty, `Cartridge::load` rejects it.
+#[derive(Debug, Clone, Copy)]
+struct PrgBanks8K(usize);
+
+impl PrgBanks8K {
+    fn last(self) -> usize {
+        self.0.saturating_sub(1)
+    }
+
+    fn second_last(self) -> usize {
+        self.0.saturating_sub(2)
+    }
+
+    /// Offset in PRG ROM of `addr` within `bank`, banks past the end wrap around
+    fn offset(self, bank: usize, addr: u16) -> usize {
+        (bank % self.0) * PRG_8K_BANK_SIZE + usize::from(addr & 0x1FFF)
+    }
+
+    fn read(self, bank: usize, addr: u16) -> CartridgeReadTarget {
+        CartridgeReadTarget::PrgRom(self.offset(bank, addr))
+    }
+}
+
 trait Mapper: Send + Sync {
     fn cpu_map_read(&self, addr: u16) -> CartridgeReadTarget;
     fn cpu_map_write(&mut self, addr: u16, data: u8);
     fn ppu_map_read(&self, addr: u16) -> usize;
     fn ppu_map_write(&self, addr: u16) -> Option<usize>;
     fn mirroring(&self) -> Mirroring;
+    /// Whole PRG RAM, `None` if the board has none
     fn get_sram(&self) -> Option<&[u8]>;
+    fn get_sram_mut(&mut self) -> Option<&mut [u8]>;
 
     fn irq_state(&self) -> bool {
         false
     }
     fn irq_clear(&mut self) {}
-    fn irq_scanline(&mut self) {}
+    fn irq_scanline(&mut self, _scanline: i16) {}
+    fn cpu_clock(&mut self) {}
+
+    /// Called after the CPU reads from the cartridge, for registers with read side effects
+    fn cpu_read_side_effects(&mut self, _addr: u16) {}
+
+    /// Called when the console is reset, for boards that latch the reset line
+    fn on_reset(&mut self) {}
+
+    /// Lets boards with their own nametable logic route nametable accesses
+    fn nametable_source(&self, _addr: u16) -> NametableSource {
+        NametableSource::Mirroring
+    }
+    /// Reads a nametable routed to `NametableSource::Mapper`
+    fn read_nametable(&self, _addr: u16) -> u8 {
+        0
+    }
+    /// Writes a nametable routed to `NametableSource::Mapper`
+    fn write_nametable(&mut self, _addr: u16, _data: u8) {}
 }
 
 pub struct Cartridge {
@@ -66,13 +218,44 @@ pub struct Cartridge {
     prg_memory: Vec<u8>, // program ROM, used by CPU
     chr_memory: Vec<u8>, // character ROM, used by PPU
     mapper: Box<dyn Mapper>,
+    mirroring_override: Option<Mirroring>,
+    info: CartridgeInfo,
+    rom_hash: u64,
+
+    #[cfg(feature = "debugger")]
+    forced_prg_banks: [Option<u16>; 4],
+    #[cfg(feature = "debugger")]
+    forced_chr_banks: [Option<u16>; 8],
 }
 
-impl Cartridge {
-    pub fn load(rom: &[u8], save_data: Option<&[u8]>) -> Result<Self, RomParserError> {
-        const PRG_BANK_SIZE: usize = 16384;
-        const CHR_BANK_SIZE: usize = 8192;
+/// 64-bit FNV-1a hash of PRG ROM followed by CHR ROM
+fn hash_rom(prg_memory: &[u8], chr_memory: &[u8]) -> u64 {
+    const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    prg_memory
+        .iter()
+        .chain(chr_memory)
+        .fold(FNV_OFFSET_BASIS, |hash, &byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+        })
+}
+
+/// ROM contents and board description, independent of the file format
+struct RomImage {
+    mapper_id: u8,
+    prg_memory: Vec<u8>,
+    /// `None` if the board uses CHR RAM
+    chr_memory: Option<Vec<u8>>,
+    mirroring: Mirroring,
+    prg_ram_size: Option<usize>,
+    chr_ram_size: Option<usize>,
+    battery: bool,
+    region: Region,
+}
 
+impl RomImage {
+    fn from_ines(rom: &[u8]) -> Result<Self, RomParserError> {
         let header: INesHeader = INesHeader::try_from(rom)?;
 
         log::info!("ROM info: {:?}", &header);
@@ -85,75 +268,348 @@ impl Cartridge {
             Mirroring::Horizontal
         };
 
-        let mapper: Box<dyn Mapper> = match header.mapper_id {
-            0 => Box::new(Mapper000::new(header.prg_size, mirroring)),
-            1 => Box::new(Mapper001::new(header.prg_size, mirroring, save_data)),
-            2 => Box::new(Mapper002::new(header.prg_size, mirroring)),
-            3 => Box::new(Mapper003::new(header.prg_size, mirroring)),
-            4 => Box::new(Mapper004::new(header.prg_size, mirroring)),
-            66 => Box::new(Mapper066::new(mirroring)),
-            _ => return Err(RomParserError::MapperNotImplemented),
-        };
-
         let chr_memory_len = CHR_BANK_SIZE * (header.chr_size as usize);
         let prg_memory_len = PRG_BANK_SIZE * (header.prg_size as usize);
 
+        if prg_memory_len == 0 {
+            log::error!("Invalid ROM: the header declares no PRG memory");
+            return Err(RomParserError::PrgSizeMismatch {
+                expected: PRG_BANK_SIZE,
+                actual: 0,
+            });
+        }
+
         let prg_start = if header.flags6.contains(Flags6::TRAINER) {
             512 + 16
         } else {
             16
         };
 
-        let expected_rom_size = prg_start + prg_memory_len + chr_memory_len;
-        if rom.len() < expected_rom_size {
-            log::error!(
-                "Invalid ROM size: expected {} bytes of memory, but ROM has {}",
-                expected_rom_size,
-                rom.len()
-            );
+        if rom.len() < prg_start {
             return Err(RomParserError::TooShort);
         }
 
-        // PRG memory
         let prg_end = prg_start + prg_memory_len;
-        let prg_memory = rom[prg_start..prg_end].to_vec();
-        assert_eq!(prg_memory.len(), prg_memory_len);
+        if rom.len() < prg_end {
+            log::error!(
+                "Invalid ROM size: expected {} bytes of PRG memory, but ROM has {}",
+                prg_memory_len,
+                rom.len() - prg_start
+            );
+            return Err(RomParserError::PrgSizeMismatch {
+                expected: prg_memory_len,
+                actual: rom.len() - prg_start,
+            });
+        }
+
+        let expected_rom_size = prg_end + chr_memory_len;
+        if rom.len() < expected_rom_size {
+            log::error!(
+                "Invalid ROM size: expected {} bytes of CHR memory, but ROM has {}",
+                chr_memory_len,
+                rom.len() - prg_end
+            );
+            return Err(RomParserError::ChrSizeMismatch {
+                expected: chr_memory_len,
+                actual: rom.len() - prg_end,
+            });
+        } else if rom.len() > expected_rom_size {
+            log::warn!(
+                "ROM has {} unexpected trailing bytes, ignoring them",
+                rom.len() - expected_rom_size
+            );
+        }
 
         // CHR memory
         // Don't parse if it's RAM
-        let chr_ram = header.chr_size == 0;
-        let chr_memory = if !chr_ram {
-            let chr_start = prg_end;
-            let chr_end = prg_end + chr_memory_len;
-            rom[chr_start..chr_end].to_vec()
+        let chr_memory = if header.chr_size != 0 {
+            Some(rom[prg_end..expected_rom_size].to_vec())
         } else {
-            vec![0u8; CHR_BANK_SIZE]
+            None
+        };
+
+        Ok(RomImage {
+            mapper_id: header.mapper_id,
+            prg_memory: rom[prg_start..prg_end].to_vec(),
+            chr_memory,
+            mirroring,
+            prg_ram_size: header.prg_ram_size,
+            chr_ram_size: header.chr_ram_size,
+            battery: header.flags6.contains(Flags6::PRG_RAM),
+            region: header
+                .region
+                .unwrap_or(if header.flags9.contains(Flags9::TV_SYSTEM) {
+                    Region::Pal
+                } else {
+                    Region::Ntsc
+                }),
+        })
+    }
+
+    fn from_unif(rom: &[u8]) -> Result<Self, RomParserError> {
+        let unif = UnifRom::try_from(rom)?;
+
+        log::info!(
+            "UNIF board: {}, PRG: {} bytes, CHR: {} bytes",
+            unif.board,
+            unif.prg.len(),
+            unif.chr.len()
+        );
+
+        let mapper_id = unif.mapper_id().ok_or_else(|| {
+            log::error!("Unknown UNIF board: {:?}", unif.board);
+            RomParserError::UnknownBoard
+        })?;
+
+        // Mappers bank PRG ROM by 16K units
+        let prg_len = unif.prg.len();
+        if prg_len == 0 || prg_len % PRG_BANK_SIZE != 0 {
+            return Err(RomParserError::PrgSizeMismatch {
+                expected: (prg_len / PRG_BANK_SIZE + 1) * PRG_BANK_SIZE,
+                actual: prg_len,
+            });
+        }
+
+        Ok(RomImage {
+            mapper_id,
+            prg_memory: unif.prg,
+            chr_memory: if unif.chr.is_empty() {
+                None
+            } else {
+                Some(unif.chr)
+            },
+            mirroring: unif.mirroring.unwrap_or(Mirroring::Horizontal),
+            prg_ram_size: None,
+            chr_ram_size: None,
+            battery: unif.battery,
+            region: unif.region,
+        })
+    }
+}
+
+/// Builds the mapper of a board in its power-up state
+fn new_mapper(
+    mapper_id: u8,
+    prg_rom_size: usize,
+    prg_ram_size: usize,
+    mirroring: Mirroring,
+    save_data: Option<&[u8]>,
+) -> Result<Box<dyn Mapper>, RomParserError> {
+    let prg_size = (prg_rom_size / PRG_BANK_SIZE) as u8;
+    let prg_banks_8k = PrgBanks8K(prg_rom_size / PRG_8K_BANK_SIZE);
+
+    let mapper: Box<dyn Mapper> = match mapper_id {
+        0 => Box::new(Mapper000::new(prg_size, mirroring)),
+        1 => Box::new(Mapper001::new(prg_size, prg_ram_size, mirroring, save_data)),
+        2 => Box::new(Mapper002::new(prg_size, mirroring)),
+        3 => Box::new(Mapper003::new(prg_size, mirroring)),
+        4 => Box::new(Mapper004::new(prg_size, prg_ram_size, mirroring)),
+        5 => Box::new(Mapper005::new(prg_banks_8k, prg_ram_size)),
+        21 | 22 | 23 | 25 => Box::new(Mapper021::new(
+            mapper_id,
+            prg_banks_8k,
+            prg_ram_size,
+            mirroring,
+        )),
+        66 => Box::new(Mapper066::new(mirroring)),
+        69 => Box::new(Mapper069::new(prg_banks_8k, prg_ram_size, mirroring)),
+        85 => Box::new(Mapper085::new(prg_banks_8k, prg_ram_size, mirroring)),
+        _ => return Err(RomParserError::MapperNotImplemented),
+    };
+
+    Ok(mapper)
+}
+
+impl Cartridge {
+    /// Loads an iNES, NES 2.0 or UNIF ROM
+    pub fn load(rom: &[u8], save_data: Option<&[u8]>) -> Result<Self, RomParserError> {
+        let image = match detect_format(rom) {
+            Some(RomFormat::Unif) => RomImage::from_unif(rom)?,
+            _ => RomImage::from_ines(rom)?,
         };
 
+        let mirroring = image.mirroring;
+
+        // iNES headers don't reliably declare the PRG RAM size, assume a single 8K window
+        let prg_ram_size = image
+            .prg_ram_size
+            .unwrap_or(PRG_RAM_WINDOW_SIZE)
+            .max(PRG_RAM_WINDOW_SIZE);
+
+        let mapper = new_mapper(
+            image.mapper_id,
+            image.prg_memory.len(),
+            prg_ram_size,
+            mirroring,
+            save_data,
+        )?;
+
+        // Boards without CHR ROM have CHR RAM. Only NES 2.0 headers declare its size, and
+        // dumps missing their CHR ROM end up here too, so always provide at least 8K.
+        let chr_ram = image.chr_memory.is_none();
+        let chr_ram_size = image.chr_ram_size;
+        let chr_memory = image.chr_memory.unwrap_or_else(|| {
+            let size = chr_ram_size.unwrap_or(CHR_BANK_SIZE).max(CHR_BANK_SIZE);
+            log::info!("No CHR ROM, allocating {} bytes of CHR RAM", size);
+            vec![0u8; size]
+        });
+
+        let info = CartridgeInfo {
+            mapper: image.mapper_id,
+            prg_rom_size: image.prg_memory.len(),
+            chr_rom_size: if chr_ram { 0 } else { chr_memory.len() },
+            chr_ram_size: if chr_ram { chr_memory.len() } else { 0 },
+            mirroring,
+            prg_ram_size,
+            battery: image.battery,
+            region: image.region,
+        };
+
+        // CHR RAM contents are not part of the ROM
+        let rom_hash = hash_rom(&image.prg_memory, if chr_ram { &[] } else { &chr_memory });
+
         Ok(Cartridge {
             chr_ram,
-            prg_memory,
+            prg_memory: image.prg_memory,
             chr_memory,
             mapper,
+            mirroring_override: None,
+            info,
+            rom_hash,
+
+            #[cfg(feature = "debugger")]
+            forced_prg_banks: [None; 4],
+            #[cfg(feature = "debugger")]
+            forced_chr_banks: [None; 8],
         })
     }
 
+    /// Replaces PRG and CHR ROM with the ones of `rom`, keeping mapper and RAM state.
+    /// The new ROM must use the same mapper and memory sizes.
+    pub fn reload_prg_chr(&mut self, rom: &[u8]) -> Result<(), RomParserError> {
+        let reloaded = Cartridge::load(rom, None)?;
+        let (old, new) = (self.info, reloaded.info);
+        if old.mapper != new.mapper
+            || old.prg_rom_size != new.prg_rom_size
+            || old.chr_rom_size != new.chr_rom_size
+        {
+            return Err(RomParserError::IncompatibleReload);
+        }
+
+        self.prg_memory = reloaded.prg_memory;
+        if !self.chr_ram {
+            self.chr_memory = reloaded.chr_memory;
+        }
+        self.rom_hash = reloaded.rom_hash;
+
+        Ok(())
+    }
+
+    /// Rebuilds the mapper in its power-up state, keeping PRG RAM
+    pub fn power_cycle(&mut self) {
+        let info = self.info;
+        let mut mapper = new_mapper(
+            info.mapper,
+            info.prg_rom_size,
+            info.prg_ram_size,
+            info.mirroring,
+            None,
+        )
+        .expect("the mapper was built when the cartridge was loaded");
+
+        if let (Some(old), Some(new)) = (self.mapper.get_sram(), mapper.get_sram_mut()) {
+            new.copy_from_slice(old);
+        }
+        self.mapper = mapper;
+    }
+
+    pub fn info(&self) -> CartridgeInfo {
+        self.info
+    }
+
+    /// Stable identifier of the ROM, hashed from its PRG and CHR ROM data
+    pub fn rom_hash(&self) -> u64 {
+        self.rom_hash
+    }
+
     pub fn mirroring(&self) -> Mirroring {
-        self.mapper.mirroring()
+        self.mirroring_override
+            .unwrap_or_else(|| self.mapper.mirroring())
+    }
+
+    pub fn set_mirroring_override(&mut self, mirroring: Option<Mirroring>) {
+        self.mirroring_override = mirroring;
+    }
+
+    /// Maps the 8K PRG `bank` in `slot` (0 to 3, for $8000, $A000, $C000 and $E000)
+    /// for reads, whatever the mapper selected. Writes still go to the mapper.
+    #[cfg(feature = "debugger")]
+    pub fn force_prg_bank(&mut self, slot: u8, bank: u16) {
+        match self.forced_prg_banks.get_mut(slot as usize) {
+            Some(forced) => *forced = Some(bank),
+            None => log::warn!("Invalid PRG slot {}, there are 4 slots of 8K", slot),
+        }
+    }
+
+    /// Maps the 1K CHR `bank` in `slot` (0 to 7, for $0000 to $1C00) for reads,
+    /// whatever the mapper selected
+    #[cfg(feature = "debugger")]
+    pub fn force_chr_bank(&mut self, slot: u8, bank: u16) {
+        match self.forced_chr_banks.get_mut(slot as usize) {
+            Some(forced) => *forced = Some(bank),
+            None => log::warn!("Invalid CHR slot {}, there are 8 slots of 1K", slot),
+        }
+    }
+
+    /// Gives bank selection back to the mapper
+    #[cfg(feature = "debugger")]
+    pub fn clear_forced_banks(&mut self) {
+        self.forced_prg_banks = [None; 4];
+        self.forced_chr_banks = [None; 8];
+    }
+
+    /// Reads from a forced bank, banks past the end of memory wrap around
+    #[cfg(feature = "debugger")]
+    fn read_forced_bank(memory: &[u8], bank: u16, bank_size: usize, addr: u16) -> u8 {
+        let offset = bank as usize * bank_size + (addr as usize & (bank_size - 1));
+        memory[offset % memory.len()]
     }
 
     pub fn read_prg_mem(&self, addr: u16) -> u8 {
+        #[cfg(feature = "debugger")]
+        if addr >= 0x8000 {
+            let slot = ((addr - 0x8000) as usize) / FORCED_PRG_BANK_SIZE;
+            if let Some(bank) = self.forced_prg_banks[slot] {
+                return Self::read_forced_bank(&self.prg_memory, bank, FORCED_PRG_BANK_SIZE, addr);
+            }
+        }
+
         match self.mapper.cpu_map_read(addr) {
             CartridgeReadTarget::PrgRom(rom_addr) => self.prg_memory[rom_addr],
             CartridgeReadTarget::PrgRam(data) => data,
         }
     }
 
+    /// Reads like the CPU does, applying the mapper's read side effects.
+    /// `read_prg_mem` stays side effect free for debugger peeks.
+    pub fn cpu_read_prg_mem(&mut self, addr: u16) -> u8 {
+        let data = self.read_prg_mem(addr);
+        self.mapper.cpu_read_side_effects(addr);
+        data
+    }
+
     pub fn write_prg_mem(&mut self, addr: u16, data: u8) {
         self.mapper.cpu_map_write(addr, data);
     }
 
     pub fn read_chr_mem(&self, addr: u16) -> u8 {
+        #[cfg(feature = "debugger")]
+        if addr < 0x2000 {
+            if let Some(bank) = self.forced_chr_banks[addr as usize / FORCED_CHR_BANK_SIZE] {
+                return Self::read_forced_bank(&self.chr_memory, bank, FORCED_CHR_BANK_SIZE, addr);
+            }
+        }
+
         let addr = self.mapper.ppu_map_read(addr);
         if addr < self.chr_memory.len() {
             self.chr_memory[addr]
@@ -164,13 +620,17 @@ impl Cartridge {
 
     pub fn write_chr_mem(&mut self, addr: u16, data: u8) {
         if self.chr_ram {
-            if let Some(addr) = self.mapper.ppu_map_write(addr) {
-                self.chr_memory[addr] = data;
-            } else {
-                log::warn!(
+            match self.mapper.ppu_map_write(addr) {
+                Some(addr) if addr < self.chr_memory.len() => self.chr_memory[addr] = data,
+                // Bank selected past the end of CHR RAM
+                Some(_) => log::warn!(
+                    "attempted to write on CHR memory at {}, outside of CHR RAM",
+                    addr
+                ),
+                None => log::warn!(
                     "attempted to write on CHR memory at {}, but this is not supported by this mapper",
                     addr
-                );
+                ),
             }
         } else {
             log::warn!(
@@ -184,14 +644,44 @@ impl Cartridge {
         self.mapper.get_sram()
     }
 
+    /// Whether the mapper asserts its IRQ, without acknowledging it
+    #[cfg(feature = "debugger")]
+    pub fn irq_state(&self) -> bool {
+        self.mapper.irq_state()
+    }
+
     pub fn take_irq_set_state(&mut self) -> bool {
         let state = self.mapper.irq_state();
         self.mapper.irq_clear();
         state
     }
 
-    pub fn irq_scanline(&mut self) {
-        self.mapper.irq_scanline()
+    pub fn irq_scanline(&mut self, scanline: i16) {
+        self.mapper.irq_scanline(scanline)
+    }
+
+    pub fn nametable_source(&self, addr: u16) -> NametableSource {
+        if self.mirroring_override.is_some() {
+            NametableSource::Mirroring
+        } else {
+            self.mapper.nametable_source(addr)
+        }
+    }
+
+    pub fn read_nametable(&self, addr: u16) -> u8 {
+        self.mapper.read_nametable(addr)
+    }
+
+    pub fn write_nametable(&mut self, addr: u16, data: u8) {
+        self.mapper.write_nametable(addr, data)
+    }
+
+    pub fn cpu_clock(&mut self) {
+        self.mapper.cpu_clock()
+    }
+
+    pub fn reset(&mut self) {
+        self.mapper.on_reset()
     }
 
     #[cfg(feature = "debugger")]
@@ -205,3 +695,324 @@ impl Cartridge {
         disas1
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rom(prg_size: u8, chr_size: u8, len: usize) -> Vec<u8> {
+        let mut rom = vec![0u8; len];
+        rom[..6].copy_from_slice(&[0x4E, 0x45, 0x53, 0x1A, prg_size, chr_size]);
+        rom
+    }
+
+    struct ResetCountingMapper(alloc::sync::Arc<core::sync::atomic::AtomicU32>);
+
+    impl Mapper for ResetCountingMapper {
+        fn cpu_map_read(&self, addr: u16) -> CartridgeReadTarget {
+            CartridgeReadTarget::PrgRom(usize::from(addr & 0x3FFF))
+        }
+        fn cpu_map_write(&mut self, _addr: u16, _data: u8) {}
+        fn ppu_map_read(&self, addr: u16) -> usize {
+            usize::from(addr)
+        }
+        fn ppu_map_write(&self, _addr: u16) -> Option<usize> {
+            None
+        }
+        fn mirroring(&self) -> Mirroring {
+            Mirroring::Horizontal
+        }
+        fn get_sram(&self) -> Option<&[u8]> {
+            None
+        }
+        fn get_sram_mut(&mut self) -> Option<&mut [u8]> {
+            None
+        }
+        fn on_reset(&mut self) {
+            self.0.fetch_add(1, core::sync::atomic::Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn mapper_sees_reset_button_only() {
+        use core::sync::atomic::Ordering;
+
+        let rom = rom(1, 1, 16 + 0x4000 + 0x2000);
+        let mut emu = crate::Emulator::new(&rom, None).unwrap();
+        let resets = alloc::sync::Arc::new(core::sync::atomic::AtomicU32::new(0));
+        emu.cartridge.mapper = Box::new(ResetCountingMapper(resets.clone()));
+
+        // Power cycles rebuild the mapper, the old one must not see a reset first
+        emu.power_cycle();
+        assert_eq!(resets.load(Ordering::Relaxed), 0);
+
+        emu.cartridge.mapper = Box::new(ResetCountingMapper(resets.clone()));
+        emu.reload_prg_chr(&rom).unwrap();
+        assert_eq!(resets.load(Ordering::Relaxed), 0);
+        emu.reset();
+        assert_eq!(resets.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn power_cycle_resets_mapper() {
+        // FME-7 with 4 PRG banks, bank 1 starts with $EA
+        let mut rom = rom(2, 1, 16 + 0x8000 + 0x2000);
+        rom[6] = 0x50;
+        rom[7] = 0x40;
+        rom[16 + 0x2000] = 0xEA;
+        let mut cartridge = Cartridge::load(&rom, None).unwrap();
+
+        cartridge.write_prg_mem(0x8000, 0x09);
+        cartridge.write_prg_mem(0xA000, 0x01);
+        cartridge.write_prg_mem(0x8000, 0x08);
+        cartridge.write_prg_mem(0xA000, 0xC0);
+        cartridge.write_prg_mem(0x6000, 0x55);
+        assert_eq!(cartridge.read_prg_mem(0x8000), 0xEA);
+
+        cartridge.power_cycle();
+        assert_eq!(cartridge.read_prg_mem(0x8000), 0x00);
+        assert_eq!(cartridge.get_save_data().unwrap()[0], 0x55);
+    }
+
+    #[test]
+    fn prg_size_mismatch() {
+        let result = Cartridge::load(&rom(2, 1, 16 + 0x4000), None);
+        assert!(matches!(
+            result,
+            Err(RomParserError::PrgSizeMismatch {
+                expected: 0x8000,
+                actual: 0x4000
+            })
+        ));
+    }
+
+    #[test]
+    fn empty_prg_rom() {
+        let result = Cartridge::load(&rom(0, 1, 16 + 0x2000), None);
+        assert!(matches!(
+            result,
+            Err(RomParserError::PrgSizeMismatch {
+                expected: 0x4000,
+                actual: 0
+            })
+        ));
+    }
+
+    #[test]
+    fn prg_banks_8k() {
+        let banks = PrgBanks8K(1);
+        assert_eq!(banks.last(), 0);
+        assert_eq!(banks.second_last(), 0);
+        assert_eq!(banks.offset(5, 0xE123), 0x0123);
+
+        let banks = PrgBanks8K(512);
+        assert_eq!(banks.last(), 511);
+        assert_eq!(banks.offset(banks.second_last(), 0xC000), 510 * 0x2000);
+    }
+
+    #[test]
+    fn chr_size_mismatch() {
+        let result = Cartridge::load(&rom(1, 1, 16 + 0x4000 + 0x1000), None);
+        assert!(matches!(
+            result,
+            Err(RomParserError::ChrSizeMismatch {
+                expected: 0x2000,
+                actual: 0x1000
+            })
+        ));
+    }
+
+    #[test]
+    fn trailing_bytes_are_tolerated() {
+        assert!(Cartridge::load(&rom(1, 1, 16 + 0x4000 + 0x2000 + 0x80), None).is_ok());
+    }
+
+    #[test]
+    fn nrom_chr_ram() {
+        let mut cartridge = Cartridge::load(&rom(1, 0, 16 + 0x4000), None).unwrap();
+        assert_eq!(cartridge.info().chr_rom_size, 0);
+        assert_eq!(cartridge.info().chr_ram_size, 0x2000);
+
+        cartridge.write_chr_mem(0x0000, 0x12);
+        cartridge.write_chr_mem(0x1FFF, 0x34);
+        assert_eq!(cartridge.read_chr_mem(0x0000), 0x12);
+        assert_eq!(cartridge.read_chr_mem(0x1FFF), 0x34);
+
+        // CHR ROM can't be written
+        let mut cartridge = Cartridge::load(&rom(1, 1, 16 + 0x4000 + 0x2000), None).unwrap();
+        cartridge.write_chr_mem(0x0000, 0x12);
+        assert_eq!(cartridge.read_chr_mem(0x0000), 0x00);
+    }
+
+    #[test]
+    fn chr_ram_bank_out_of_range() {
+        let mut cnrom = rom(1, 0, 16 + 0x4000);
+        cnrom[6] = 0x30; // Mapper 3
+        let mut cartridge = Cartridge::load(&cnrom, None).unwrap();
+
+        // Only 8K of CHR RAM, bank 2 doesn't exist
+        cartridge.write_prg_mem(0x8000, 0x02);
+        cartridge.write_chr_mem(0x0000, 0x12);
+        assert_eq!(cartridge.read_chr_mem(0x0000), 0x00);
+    }
+
+    #[cfg(feature = "debugger")]
+    #[test]
+    fn forced_banks() {
+        let mut data = rom(2, 1, 16 + 0x8000 + 0x2000);
+        data[16 + 0x6000] = 0x11; // PRG bank 3
+        data[16 + 0x8000 + 0x0C00] = 0x22; // CHR bank 3
+        let mut cartridge = Cartridge::load(&data, None).unwrap();
+
+        cartridge.force_prg_bank(0, 3);
+        cartridge.force_chr_bank(7, 3);
+        assert_eq!(cartridge.read_prg_mem(0x8000), 0x11);
+        assert_eq!(cartridge.read_chr_mem(0x1C00), 0x22);
+        assert_eq!(cartridge.read_chr_mem(0x0C00), 0x22);
+
+        // Banks wrap around
+        cartridge.force_prg_bank(1, 7);
+        assert_eq!(cartridge.read_prg_mem(0xA000), 0x11);
+
+        cartridge.clear_forced_banks();
+        assert_eq!(cartridge.read_prg_mem(0x8000), 0x00);
+        assert_eq!(cartridge.read_chr_mem(0x1C00), 0x00);
+    }
+
+    #[test]
+    fn nes2_prg_ram_size() {
+        let mut nes2 = rom(1, 1, 16 + 0x4000 + 0x2000);
+        nes2[6] = 0x10; // Mapper 1
+        nes2[7] = 0x08; // NES 2.0
+        nes2[10] = 0x79; // 32K volatile + 8K non-volatile
+        let cartridge = Cartridge::load(&nes2, None).unwrap();
+        assert_eq!(cartridge.info().prg_ram_size, 0xA000);
+        assert_eq!(cartridge.get_save_data().map(<[u8]>::len), Some(0xA000));
+
+        let ines = rom(1, 1, 16 + 0x4000 + 0x2000);
+        let cartridge = Cartridge::load(&ines, None).unwrap();
+        assert_eq!(cartridge.info().prg_ram_size, 0x2000);
+    }
+
+    #[test]
+    fn region() {
+        let mut data = rom(1, 1, 16 + 0x4000 + 0x2000);
+        data[9] = 0x01;
+        let cartridge = Cartridge::load(&data, None).unwrap();
+        assert_eq!(cartridge.info().region, Region::Pal);
+
+        // NES 2.0 uses byte 12 instead, byte 9 holds the ROM size MSBs
+        data[7] = 0x08;
+        let cartridge = Cartridge::load(&data, None).unwrap();
+        assert_eq!(cartridge.info().region, Region::Ntsc);
+        data[9] = 0x00;
+        data[12] = 0x01;
+        let cartridge = Cartridge::load(&data, None).unwrap();
+        assert_eq!(cartridge.info().region, Region::Pal);
+    }
+
+    #[test]
+    fn nes2_chr_ram_size() {
+        let mut nes2 = rom(1, 0, 16 + 0x4000);
+        nes2[6] = 0x20; // Mapper 2
+        nes2[7] = 0x08; // NES 2.0
+        nes2[11] = 0x09; // 32K volatile
+        let mut cartridge = Cartridge::load(&nes2, None).unwrap();
+        assert_eq!(cartridge.info().chr_ram_size, 0x8000);
+        cartridge.write_chr_mem(0x1FFF, 0x34);
+        assert_eq!(cartridge.read_chr_mem(0x1FFF), 0x34);
+
+        // Too small to hold the 8K window
+        nes2[11] = 0x05;
+        let cartridge = Cartridge::load(&nes2, None).unwrap();
+        assert_eq!(cartridge.info().chr_ram_size, 0x2000);
+    }
+
+    #[test]
+    fn reload_prg_chr() {
+        let mut cartridge = Cartridge::load(&rom(1, 1, 16 + 0x4000 + 0x2000), None).unwrap();
+
+        let mut rebuilt = rom(1, 1, 16 + 0x4000 + 0x2000);
+        rebuilt[16] = 0xEA;
+        rebuilt[16 + 0x4000] = 0x55;
+        cartridge.reload_prg_chr(&rebuilt).unwrap();
+        assert_eq!(cartridge.read_prg_mem(0x8000), 0xEA);
+        assert_eq!(cartridge.read_chr_mem(0x0000), 0x55);
+
+        let bigger = rom(2, 1, 16 + 0x8000 + 0x2000);
+        assert!(matches!(
+            cartridge.reload_prg_chr(&bigger),
+            Err(RomParserError::IncompatibleReload)
+        ));
+    }
+
+    fn unif(board: &[u8], prg: &[u8]) -> Vec<u8> {
+        let mut rom = vec![0u8; 32];
+        rom[..4].copy_from_slice(b"UNIF");
+        for (id, body) in &[(b"MAPR", board), (b"MIRR", &[1]), (b"PRG0", prg)] {
+            rom.extend_from_slice(*id);
+            rom.extend_from_slice(&(body.len() as u32).to_le_bytes());
+            rom.extend_from_slice(body);
+        }
+        rom
+    }
+
+    #[test]
+    fn load_unif() {
+        let mut prg = vec![0u8; 0x4000];
+        prg[0] = 0xEA;
+        let cartridge = Cartridge::load(&unif(b"NES-NROM-128\0", &prg), None).unwrap();
+        let info = cartridge.info();
+        assert_eq!(info.mapper, 0);
+        assert_eq!(info.prg_rom_size, 0x4000);
+        assert_eq!(info.chr_rom_size, 0);
+        assert!(matches!(info.mirroring, Mirroring::Vertical));
+        assert_eq!(cartridge.read_prg_mem(0xC000), 0xEA);
+
+        assert!(matches!(
+            Cartridge::load(&unif(b"UNL-NOT-A-BOARD\0", &prg), None),
+            Err(RomParserError::UnknownBoard)
+        ));
+        assert!(matches!(
+            Cartridge::load(&unif(b"NES-NROM-128\0", &prg)[..60], None),
+            Err(RomParserError::TooShort)
+        ));
+
+        // Chunk length past the end, which can't be added to the offset on 32-bit targets
+        let mut rom = unif(b"NES-NROM-128\0", &prg);
+        rom.extend_from_slice(b"CHR0\xFF\xFF\xFF\xFF");
+        assert!(matches!(
+            Cartridge::load(&rom, None),
+            Err(RomParserError::TooShort)
+        ));
+    }
+
+    #[test]
+    fn rom_hash() {
+        let mut data = rom(1, 1, 16 + 0x4000 + 0x2000);
+        let hash = Cartridge::load(&data, None).unwrap().rom_hash();
+        assert_eq!(hash, Cartridge::load(&data, None).unwrap().rom_hash());
+
+        // The header isn't hashed
+        data[6] = 0x01;
+        assert_eq!(hash, Cartridge::load(&data, None).unwrap().rom_hash());
+
+        data[16 + 0x4000 + 0x1FFF] = 0x01;
+        assert_ne!(hash, Cartridge::load(&data, None).unwrap().rom_hash());
+
+        assert_eq!(hash_rom(b"", b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(hash_rom(b"a", b""), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn detect_rom_format() {
+        let mut ines = rom(1, 1, 16);
+        assert_eq!(detect_format(&ines), Some(RomFormat::INes));
+        ines[7] = 0x08;
+        assert_eq!(detect_format(&ines), Some(RomFormat::Nes2));
+        assert_eq!(detect_format(b"NESM\x1A\x01"), Some(RomFormat::Nsf));
+        assert_eq!(detect_format(b"FDS\x1A\x02"), Some(RomFormat::Fds));
+        assert_eq!(detect_format(b"UNIF"), Some(RomFormat::Unif));
+        assert_eq!(detect_format(b"NES"), None);
+    }
+}
@@ -0,0 +1,134 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+use crate::cartridge::{Mirroring, Region, RomParserError};
+
+const HEADER_SIZE: usize = 32;
+const CHUNK_HEADER_SIZE: usize = 8;
+
+/// Contents of a UNIF file
+/// https://wiki.nesdev.com/w/index.php/UNIF
+#[derive(Debug)]
+pub struct UnifRom {
+    /// Board name from the MAPR chunk
+    pub board: String,
+    /// PRG0 to PRGF chunks, concatenated in order
+    pub prg: Vec<u8>,
+    /// CHR0 to CHRF chunks, concatenated in order. Empty if the board uses CHR RAM.
+    pub chr: Vec<u8>,
+    /// Hardwired mirroring, `None` if it is controlled by the mapper or not declared
+    pub mirroring: Option<Mirroring>,
+    pub battery: bool,
+    pub region: Region,
+}
+
+impl UnifRom {
+    /// iNES mapper implementing the board, `None` for unknown boards
+    pub fn mapper_id(&self) -> Option<u8> {
+        // Prefixes only tell who made the board
+        let board = ["NES-", "UNL-", "HVC-", "BTL-", "BMC-"]
+            .iter()
+            .find_map(|prefix| self.board.strip_prefix(prefix))
+            .unwrap_or(&self.board);
+
+        let mapper_id = match board {
+            "NROM" | "NROM-128" | "NROM-256" | "RROM" | "RROM-128" => 0,
+            "SAROM" | "SBROM" | "SCROM" | "SEROM" | "SGROM" | "SKROM" | "SLROM" | "SL1ROM"
+            | "SNROM" | "SOROM" | "SUROM" | "SXROM" => 1,
+            "UNROM" | "UOROM" => 2,
+            "CNROM" => 3,
+            "TBROM" | "TEROM" | "TFROM" | "TGROM" | "TKROM" | "TLROM" | "TNROM" | "TSROM"
+            | "TR1ROM" | "HKROM" => 4,
+            "EKROM" | "ELROM" | "ETROM" | "EWROM" => 5,
+            "GNROM" | "MHROM" => 66,
+            "JLROM" | "JSROM" => 69,
+            _ => return None,
+        };
+        Some(mapper_id)
+    }
+}
+
+impl TryFrom<&[u8]> for UnifRom {
+    type Error = RomParserError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        if data.len() < HEADER_SIZE {
+            return Err(RomParserError::TooShort);
+        }
+
+        if !data.starts_with(b"UNIF") {
+            return Err(RomParserError::InvalidMagicBytes);
+        }
+
+        let mut board = String::new();
+        let mut prg_chunks: [&[u8]; 16] = [&[]; 16];
+        let mut chr_chunks: [&[u8]; 16] = [&[]; 16];
+        let mut mirroring = None;
+        let mut battery = false;
+        let mut region = Region::Ntsc;
+
+        let mut chunks = &data[HEADER_SIZE..];
+        while !chunks.is_empty() {
+            if chunks.len() < CHUNK_HEADER_SIZE {
+                return Err(RomParserError::TooShort);
+            }
+
+            let id = &chunks[..4];
+            let len = u32::from_le_bytes([chunks[4], chunks[5], chunks[6], chunks[7]]) as usize;
+            // The length is untrusted, it can overflow on 32-bit targets
+            let chunk_end = CHUNK_HEADER_SIZE
+                .checked_add(len)
+                .ok_or(RomParserError::TooShort)?;
+            let body = chunks
+                .get(CHUNK_HEADER_SIZE..chunk_end)
+                .ok_or(RomParserError::TooShort)?;
+            chunks = &chunks[chunk_end..];
+
+            // PRGn and CHRn chunks are numbered with a hexadecimal digit
+            let chunk_index = || char::from(id[3]).to_digit(16).map(|i| i as usize);
+
+            match id {
+                b"MAPR" => {
+                    // Null-terminated string
+                    let name = body.split(|&c| c == 0).next().unwrap_or_default();
+                    board = name.iter().copied().map(char::from).collect();
+                }
+                b"MIRR" => {
+                    mirroring = match body.first() {
+                        Some(0) => Some(Mirroring::Horizontal),
+                        Some(1) => Some(Mirroring::Vertical),
+                        Some(2) => Some(Mirroring::OneScreenLower),
+                        Some(3) => Some(Mirroring::OneScreenUpper),
+                        Some(4) => Some(Mirroring::FourScreen),
+                        _ => None,
+                    }
+                }
+                b"BATR" => battery = body.first() != Some(&0),
+                b"TVCI" => {
+                    if body.first() == Some(&1) {
+                        region = Region::Pal;
+                    }
+                }
+                _ if id.starts_with(b"PRG") => match chunk_index() {
+                    Some(i) => prg_chunks[i] = body,
+                    None => log::warn!("Ignoring invalid UNIF PRG chunk"),
+                },
+                _ if id.starts_with(b"CHR") => match chunk_index() {
+                    Some(i) => chr_chunks[i] = body,
+                    None => log::warn!("Ignoring invalid UNIF CHR chunk"),
+                },
+                _ => log::debug!("Ignoring UNIF chunk {:?}", id),
+            }
+        }
+
+        Ok(UnifRom {
+            board,
+            prg: prg_chunks.concat(),
+            chr: chr_chunks.concat(),
+            mirroring,
+            battery,
+            region,
+        })
+    }
+}
@@ -0,0 +1,165 @@
+use bitflags::bitflags;
+
+bitflags! {
+    /// Standard controller buttons, in the order they are reported on $4016/$4017
+    #[derive(Default)]
+    pub struct ControllerState: u8 {
+        const A = 0x80;
+        const B = 0x40;
+        const SELECT = 0x20;
+        const START = 0x10;
+        const UP = 0x08;
+        const DOWN = 0x04;
+        const LEFT = 0x02;
+        const RIGHT = 0x01;
+    }
+}
+
+/// Device plugged in a controller port
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputDevice {
+    /// Nothing is plugged in, $4016/$4017 reads return open bus
+    Disconnected,
+    /// Standard NES controller, reporting its 8 buttons serially
+    StandardController,
+    /// Famicom controller, hardwired to the console. Reports its buttons like the NES
+    /// controller, but the one in port 2 has a microphone, reported on bit 2 of $4016.
+    FamicomController,
+}
+
+/// Controller port ($4016 or $4017) with the device plugged in it
+pub struct ControllerPort {
+    device: InputDevice,
+    buttons: u8,
+    strobe: bool,
+    shift_register: u8,
+    latch: u8,
+    polled: bool, // Read since the last call to `take_polled`
+    microphone: bool,
+}
+
+impl Default for ControllerPort {
+    fn default() -> Self {
+        Self {
+            device: InputDevice::StandardController,
+            buttons: 0,
+            strobe: false,
+            shift_register: 0,
+            latch: 0,
+            polled: false,
+            microphone: false,
+        }
+    }
+}
+
+impl ControllerPort {
+    pub fn device(&self) -> InputDevice {
+        self.device
+    }
+
+    pub fn set_device(&mut self, device: InputDevice) {
+        self.device = device;
+        self.shift_register = 0;
+        self.latch = 0;
+    }
+
+    /// Buttons currently pressed, in NES button order
+    pub fn buttons(&self) -> u8 {
+        self.buttons
+    }
+
+    pub fn set_buttons(&mut self, buttons: u8) {
+        self.buttons = buttons;
+    }
+
+    /// Whether someone is speaking in the microphone, if the device has one
+    pub fn microphone(&self) -> bool {
+        self.device == InputDevice::FamicomController && self.microphone
+    }
+
+    pub fn set_microphone(&mut self, active: bool) {
+        self.microphone = active;
+    }
+
+    /// Buttons state as the game observes it, see `Emulator::controller1_report`
+    pub fn report(&self) -> u8 {
+        match self.device {
+            InputDevice::Disconnected => 0,
+            InputDevice::StandardController | InputDevice::FamicomController if self.strobe => {
+                self.buttons
+            }
+            InputDevice::StandardController | InputDevice::FamicomController => self.latch,
+        }
+    }
+
+    /// Write on $4016, bit 0 being the strobe signal shared by both ports
+    pub fn write(&mut self, data: u8) {
+        self.strobe = data & 0x01 == 0x01;
+        self.shift_register = self.buttons;
+        self.latch = self.buttons;
+    }
+
+    pub fn read(&mut self) -> u8 {
+        self.polled = true;
+        match self.device {
+            // Nothing drives the data lines, so the last value on the bus is read back:
+            // the high byte of the $4016/$4017 address
+            InputDevice::Disconnected => 0x40,
+            InputDevice::StandardController | InputDevice::FamicomController => {
+                if self.strobe {
+                    // The shift register keeps reloading, reporting the A button
+                    (self.buttons & 0x80) >> 7
+                } else {
+                    let data = (self.shift_register & 0x80) >> 7;
+                    self.shift_register <<= 1;
+                    data
+                }
+            }
+        }
+    }
+
+    /// Whether the port was read since the last call
+    pub fn take_polled(&mut self) -> bool {
+        core::mem::take(&mut self.polled)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn standard_controller_reports_serially() {
+        let mut port = ControllerPort::default();
+        port.set_buttons((ControllerState::A | ControllerState::START).bits());
+        port.write(1);
+        port.write(0);
+
+        let bits: [u8; 8] = [(); 8].map(|_| port.read());
+        assert_eq!(bits, [1, 0, 0, 1, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn strobe_reports_a_button() {
+        let mut port = ControllerPort::default();
+        port.set_buttons(ControllerState::A.bits());
+        port.write(1);
+        assert_eq!(port.read(), 1);
+        assert_eq!(port.read(), 1);
+
+        port.set_buttons(ControllerState::RIGHT.bits());
+        assert_eq!(port.read(), 0);
+    }
+
+    #[test]
+    fn disconnected_port_reads_open_bus() {
+        let mut port = ControllerPort::default();
+        port.set_device(InputDevice::Disconnected);
+        port.set_buttons(0xFF);
+        port.write(1);
+        port.write(0);
+
+        assert_eq!(port.read(), 0x40);
+        assert_eq!(port.report(), 0);
+    }
+}
@@ -5,6 +5,7 @@ use alloc::string::ToString;
 use alloc::vec::Vec;
 use core::convert::TryFrom as _;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AddressingMode {
     Accumulator,
     Immediate,
@@ -22,7 +23,7 @@ pub enum AddressingMode {
 }
 
 impl AddressingMode {
-    fn required_bytes(&self) -> usize {
+    pub fn required_bytes(&self) -> usize {
         match &self {
             AddressingMode::Accumulator => 0,
             AddressingMode::Immediate => 1,
@@ -68,6 +69,55 @@ impl AddressingMode {
     }
 }
 
+/// A single decoded instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Instruction {
+    /// Raw opcode byte
+    pub opcode: u8,
+    /// Operand bytes, only the first `size() - 1` are part of the instruction
+    pub operands: [u8; 2],
+}
+
+impl Instruction {
+    /// Decodes the instruction starting at the first byte
+    pub fn decode(bytes: [u8; 3]) -> Self {
+        Self {
+            opcode: bytes[0],
+            operands: [bytes[1], bytes[2]],
+        }
+    }
+
+    /// Addressing mode of the instruction, `None` if the opcode is unknown
+    pub fn addressing_mode(&self) -> Option<AddressingMode> {
+        Opcode::try_from(self.opcode)
+            .ok()
+            .map(|opcode| opcode.addressing_mode())
+    }
+
+    /// Size of the instruction in bytes. Unknown opcodes count as a single byte.
+    pub fn size(&self) -> usize {
+        self.addressing_mode()
+            .map(|mode| 1 + mode.required_bytes())
+            .unwrap_or(1)
+    }
+
+    /// Formats the instruction as if it was located at `address`
+    pub fn format(&self, address: u16) -> String {
+        match Opcode::try_from(self.opcode) {
+            Ok(opcode) => {
+                let mut disas = format!("{:?}", &opcode)[..3].to_lowercase();
+                let mode = opcode.addressing_mode();
+                if mode.required_bytes() > 0 {
+                    disas += " ";
+                    disas += &mode.format(&self.operands, address);
+                }
+                disas
+            }
+            Err(_) => "???".to_string(),
+        }
+    }
+}
+
 pub fn disassemble(mem: &[u8], start: u16) -> Vec<(u16, String)> {
     let mut index: usize = 0;
     let mut disassembly = Vec::new();
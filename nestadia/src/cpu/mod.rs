@@ -27,6 +27,19 @@ bitflags! {
     }
 }
 
+/// Decoded CPU status register (P)
+#[cfg(feature = "debugger")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatusFlags {
+    pub negative: bool,
+    pub overflow: bool,
+    pub break_command: bool,
+    pub decimal: bool,
+    pub interrupt_disable: bool,
+    pub zero: bool,
+    pub carry: bool,
+}
+
 #[derive(Clone, Debug)]
 pub struct Cpu {
     pub a: u8,
@@ -36,6 +49,16 @@ pub struct Cpu {
     pub pc: u16,
     pub cycles: u8,
     pub status_register: StatusRegister,
+
+    // Interrupts recognized when the lines were last polled
+    nmi_pending: bool,
+    irq_pending: bool,
+    // I flag as seen by interrupt polling, which lags behind CLI, SEI and PLP
+    irq_inhibit: bool,
+    // BRK or the IRQ sequence is running, see `can_hijack`
+    hijackable: bool,
+    // An NMI took over the running BRK or IRQ sequence, its vector is used instead
+    nmi_hijack: bool,
 }
 
 impl Default for Cpu {
@@ -44,44 +67,112 @@ impl Default for Cpu {
             a: 0,
             x: 0,
             y: 0,
+            // Power-up state, the reset sequence brings SP down to $FD
             st: 0,
             pc: 0,
             cycles: 0,
-            status_register: StatusRegister::empty(),
+            status_register: StatusRegister::U | StatusRegister::I,
+
+            nmi_pending: false,
+            irq_pending: false,
+            irq_inhibit: true,
+            hijackable: false,
+            nmi_hijack: false,
         }
     }
 }
 
 impl Cpu {
+    #[cfg(feature = "debugger")]
+    pub fn flags(&self) -> StatusFlags {
+        StatusFlags {
+            negative: self.status_register.contains(StatusRegister::N),
+            overflow: self.status_register.contains(StatusRegister::V),
+            break_command: self.status_register.contains(StatusRegister::B),
+            decimal: self.status_register.contains(StatusRegister::D),
+            interrupt_disable: self.status_register.contains(StatusRegister::I),
+            zero: self.status_register.contains(StatusRegister::Z),
+            carry: self.status_register.contains(StatusRegister::C),
+        }
+    }
+
+    /// Runs the reset sequence: A, X and Y are preserved, SP is decremented by 3
+    /// (as if PC and P were pushed, without writing) and interrupts are disabled.
     pub fn reset(&mut self, bus: &mut CpuBus<'_>) {
-        self.a = 0;
-        self.x = 0;
-        self.y = 0;
-        self.st = 0xFD;
+        self.st = self.st.wrapping_sub(3);
         self.cycles = 8;
-        self.status_register = StatusRegister::U | StatusRegister::I;
+        self.status_register
+            .insert(StatusRegister::U | StatusRegister::I);
+        self.nmi_pending = false;
+        self.irq_pending = false;
+        self.irq_inhibit = true;
+        self.hijackable = false;
+        self.nmi_hijack = false;
         self.pc = u16::from(bus.read(PC_START)) | (u16::from(bus.read(PC_START + 1)) << 8);
         // self.pc = 0xC000;
     }
 
+    /// Whether the CPU is in the second-to-last cycle of an instruction, when
+    /// interrupt lines are polled. Interrupts raised later are seen after the next instruction.
+    /// Lines are also polled while an NMI can hijack BRK or the IRQ sequence.
+    pub fn is_polling_interrupts(&self) -> bool {
+        self.cycles == 1 || self.can_hijack()
+    }
+
+    /// Whether BRK or the IRQ sequence are in their first 4 cycles, before the vector
+    /// is chosen. An NMI seen then keeps the pushed state (B set for BRK) but jumps to
+    /// the NMI vector, and is not taken again afterwards.
+    fn can_hijack(&self) -> bool {
+        self.hijackable && self.cycles >= 4
+    }
+
+    /// Whether IRQs are masked at the polling point. Changes made by CLI, SEI and PLP
+    /// only take effect after the next instruction.
+    pub fn irq_inhibited(&self) -> bool {
+        self.irq_inhibit
+    }
+
+    /// Latches the interrupt lines, the interrupt sequence starts once the
+    /// current instruction is completed
+    pub fn poll_interrupts(&mut self, nmi: bool, irq: bool) {
+        if nmi && self.can_hijack() {
+            self.nmi_hijack = true;
+            self.hijackable = false;
+        } else {
+            self.nmi_pending |= nmi;
+        }
+        self.irq_pending |= irq && !self.irq_inhibit;
+    }
+
+    /// Whether an interrupt sequence will run instead of the next instruction
+    #[cfg(feature = "debugger")]
+    pub(crate) fn interrupt_pending(&self) -> bool {
+        self.nmi_pending || self.irq_pending
+    }
+
     pub fn irq(&mut self, bus: &mut CpuBus<'_>) {
         if !self.status_register.contains(StatusRegister::I) {
-            // Push current PC
-            self.stack_push(bus, ((self.pc >> 8) & 0xff) as u8);
-            self.stack_push(bus, (self.pc & 0xff) as u8);
+            self.irq_sequence(bus);
+        }
+    }
 
-            // Push status register
-            self.status_register.remove(StatusRegister::B);
-            self.status_register.insert(StatusRegister::U);
-            self.stack_push(bus, self.status_register.bits());
+    fn irq_sequence(&mut self, bus: &mut CpuBus<'_>) {
+        // Push current PC
+        self.stack_push(bus, ((self.pc >> 8) & 0xff) as u8);
+        self.stack_push(bus, (self.pc & 0xff) as u8);
 
-            self.status_register.insert(StatusRegister::I);
+        // Push status register
+        self.status_register.remove(StatusRegister::B);
+        self.status_register.insert(StatusRegister::U);
+        self.stack_push(bus, self.status_register.bits());
 
-            self.pc =
-                u16::from(bus.read(IRQ_HANDLER)) | (u16::from(bus.read(IRQ_HANDLER + 1)) << 8);
+        self.status_register.insert(StatusRegister::I);
+        self.irq_inhibit = true;
 
-            self.cycles = 7;
-        }
+        self.pc = u16::from(bus.read(IRQ_HANDLER)) | (u16::from(bus.read(IRQ_HANDLER + 1)) << 8);
+
+        self.cycles = 7;
+        self.hijackable = true;
     }
 
     pub fn nmi(&mut self, bus: &mut CpuBus<'_>) {
@@ -95,6 +186,7 @@ impl Cpu {
         self.stack_push(bus, self.status_register.bits());
 
         self.status_register.insert(StatusRegister::I);
+        self.irq_inhibit = true;
 
         self.pc = u16::from(bus.read(NMI_HANDLER))
             | (u16::from(bus.read(NMI_HANDLER.wrapping_add(1))) << 8);
@@ -104,6 +196,24 @@ impl Cpu {
 
     pub fn clock(&mut self, bus: &mut CpuBus<'_>) {
         if self.cycles == 0 {
+            self.hijackable = false;
+        }
+
+        if self.nmi_hijack {
+            self.nmi_hijack = false;
+            self.pc = u16::from(bus.read(NMI_HANDLER))
+                | (u16::from(bus.read(NMI_HANDLER.wrapping_add(1))) << 8);
+        }
+
+        if self.cycles == 0 && self.nmi_pending {
+            self.nmi_pending = false;
+            self.nmi(bus);
+        } else if self.cycles == 0 && self.irq_pending {
+            // The IRQ was accepted when polled, even if I has been set since (SEI, PLP)
+            self.irq_pending = false;
+            self.irq_sequence(bus);
+        } else if self.cycles == 0 {
+            let irq_inhibit = self.status_register.contains(StatusRegister::I);
             let opcode = match Opcode::try_from(bus.read(self.pc)) {
                 Ok(o) => o,
                 Err(_) => {
@@ -205,7 +315,8 @@ impl Cpu {
                     self.inst_ora(op);
                 }
                 Opcode::AslAbsX => {
-                    let (addr, _) = self.am_abx(bus);
+                    let (addr, page_crossed) = self.am_abx(bus);
+                    self.dummy_read_unless_crossed(bus, addr, page_crossed);
                     let op = bus.read(addr);
                     let result = self.inst_asl(op);
                     bus.write(addr, result);
@@ -309,7 +420,8 @@ impl Cpu {
                     self.inst_and(op);
                 }
                 Opcode::RolAbsX => {
-                    let (addr, _) = self.am_abx(bus);
+                    let (addr, page_crossed) = self.am_abx(bus);
+                    self.dummy_read_unless_crossed(bus, addr, page_crossed);
                     let op = bus.read(addr);
                     let result = self.inst_rol(op);
                     bus.write(addr, result);
@@ -406,7 +518,8 @@ impl Cpu {
                     self.inst_eor(op);
                 }
                 Opcode::LsrAbsX => {
-                    let (addr, _) = self.am_abx(bus);
+                    let (addr, page_crossed) = self.am_abx(bus);
+                    self.dummy_read_unless_crossed(bus, addr, page_crossed);
                     let op = bus.read(addr);
                     let result = self.inst_lsr(op);
                     bus.write(addr, result);
@@ -503,7 +616,8 @@ impl Cpu {
                     self.inst_adc(op);
                 }
                 Opcode::RorAbsX => {
-                    let (addr, _) = self.am_abx(bus);
+                    let (addr, page_crossed) = self.am_abx(bus);
+                    self.dummy_read_unless_crossed(bus, addr, page_crossed);
                     let op = bus.read(addr);
                     let result = self.inst_ror(op);
                     bus.write(addr, result);
@@ -549,7 +663,8 @@ impl Cpu {
                     self.inst_bcc(addr);
                 }
                 Opcode::StaIndY => {
-                    let (addr, _) = self.am_izy(bus);
+                    let (addr, page_crossed) = self.am_izy(bus);
+                    self.dummy_read_unless_crossed(bus, addr, page_crossed);
                     self.inst_sta(bus, addr);
                 }
                 Opcode::StyZpX => {
@@ -568,14 +683,16 @@ impl Cpu {
                     self.inst_tya();
                 }
                 Opcode::StaAbsY => {
-                    let (addr, _) = self.am_aby(bus);
+                    let (addr, page_crossed) = self.am_aby(bus);
+                    self.dummy_read_unless_crossed(bus, addr, page_crossed);
                     self.inst_sta(bus, addr);
                 }
                 Opcode::Txs => {
                     self.inst_txs();
                 }
                 Opcode::StaAbsX => {
-                    let (addr, _) = self.am_abx(bus);
+                    let (addr, page_crossed) = self.am_abx(bus);
+                    self.dummy_read_unless_crossed(bus, addr, page_crossed);
                     self.inst_sta(bus, addr);
                 }
 
@@ -802,7 +919,8 @@ impl Cpu {
                     self.inst_cmp(op);
                 }
                 Opcode::DecAbsX => {
-                    let (addr, _) = self.am_abx(bus);
+                    let (addr, page_crossed) = self.am_abx(bus);
+                    self.dummy_read_unless_crossed(bus, addr, page_crossed);
                     let op = bus.read(addr);
                     let result = self.inst_dec(op);
                     bus.write(addr, result);
@@ -906,7 +1024,8 @@ impl Cpu {
                     self.inst_sbc(op);
                 }
                 Opcode::IncAbsX => {
-                    let (addr, _) = self.am_abx(bus);
+                    let (addr, page_crossed) = self.am_abx(bus);
+                    self.dummy_read_unless_crossed(bus, addr, page_crossed);
                     let op = bus.read(addr);
                     let result = self.inst_inc(op);
                     bus.write(addr, result);
@@ -914,6 +1033,12 @@ impl Cpu {
             };
 
             self.cycles += opcode.cycles();
+
+            // CLI, SEI and PLP change I after interrupts are polled, RTI changes it before
+            self.irq_inhibit = match opcode {
+                Opcode::Cli | Opcode::Sei | Opcode::Plp => irq_inhibit,
+                _ => self.status_register.contains(StatusRegister::I),
+            };
         }
         self.cycles -= 1;
     }
@@ -954,6 +1079,9 @@ impl Cpu {
 
         // Check if page has changed and request additionnal clock cycle
         let need_additionnal_cycle = address_no_offset & 0xff00 != address_with_offset & 0xff00;
+        if need_additionnal_cycle {
+            self.dummy_read(bus, address_no_offset, address_with_offset);
+        }
 
         (address_with_offset, need_additionnal_cycle)
     }
@@ -966,6 +1094,9 @@ impl Cpu {
 
         // Check if page has changed and request additionnal clock cycle
         let need_additionnal_cycle = address_no_offset & 0xff00 != address_with_offset & 0xff00;
+        if need_additionnal_cycle {
+            self.dummy_read(bus, address_no_offset, address_with_offset);
+        }
 
         (address_with_offset, need_additionnal_cycle)
     }
@@ -1005,10 +1136,32 @@ impl Cpu {
 
         // Check if page has changed and request additionnal clock cycle
         let need_additionnal_cycle = address_no_offset & 0xff00 != address_with_offset & 0xff00;
+        if need_additionnal_cycle {
+            self.dummy_read(bus, address_no_offset, address_with_offset);
+        }
 
         (address_with_offset, need_additionnal_cycle)
     }
 
+    /// Indexed addressing adds the index to the low byte first, and reads from there while
+    /// the high byte is fixed. The read can have side effects (e.g. on $2002 or $4016).
+    fn dummy_read(&mut self, bus: &mut CpuBus<'_>, address_no_offset: u16, address: u16) {
+        bus.read((address_no_offset & 0xff00) | (address & 0x00ff));
+    }
+
+    /// Indexed writes and read-modify-writes always do the dummy read, even without
+    /// page crossing. When the page is crossed, the addressing mode already did it.
+    fn dummy_read_unless_crossed(
+        &mut self,
+        bus: &mut CpuBus<'_>,
+        address: u16,
+        page_crossed: bool,
+    ) {
+        if !page_crossed {
+            bus.read(address);
+        }
+    }
+
     fn am_rel(&mut self, bus: &mut CpuBus<'_>) -> u16 {
         let address = bus.read(self.pc);
         self.pc = self.pc.wrapping_add(1);
@@ -1022,6 +1175,7 @@ impl Cpu {
     }
 
     // Instructions
+    // The 2A03 has no decimal mode: D can be set and pushed, but ADC and SBC ignore it
     fn inst_adc(&mut self, op: u8) {
         #![allow(clippy::many_single_char_names)]
 
@@ -1138,6 +1292,7 @@ impl Cpu {
 
         self.pc = u16::from(bus.read(IRQ_HANDLER))
             | (u16::from(bus.read(IRQ_HANDLER.wrapping_add(1))) << 8);
+        self.hijackable = true;
     }
 
     fn inst_bvc(&mut self, offset: u16) {
@@ -1528,7 +1683,7 @@ impl CpuBus<'_> {
         match addr {
             0..=0x1FFF => self.write_ram(addr, data),
             0x2000..=0x3FFF => self.write_ppu_register(addr, data),
-            0x4000..=0x4013 | 0x4015 => (), // TODO: APU
+            0x4000..=0x4013 | 0x4015 | 0x4017 => (), // TODO: APU
             0x4014 => {
                 // https://wiki.nesdev.com/w/index.php/PPU_registers#OAMDMA
                 let page_begin = u16::from(data) << 8;
@@ -1537,21 +1692,8 @@ impl CpuBus<'_> {
                     buffer[usize::from(offset)] = self.read(page_begin + offset);
                 }
                 self.write_ppu_oam_dma(&buffer);
-
-                // FIXME: this operation takes a number of cycles to perform
-                // As per nesdev wiki:
-                // "The CPU is suspended during the transfer,
-                // which will take 513 or 514 cycles after the $4014 write tick.
-                // (1 wait state cycle while waiting for writes to complete,
-                // +1 if on an odd CPU cycle, then 256 alternating read/write cycles.)"
-                // TODO: Cpu's cycle type must be changed to a `u16`
-                // Additional cycles should be computed as follow:
-                // if cycles % 2 == 1 { 514 } else { 513 }
-                // This will requires a refactor so I'm postponing this task as I need
-                // to get PPU working ASAP.
             }
-            0x4016 => self.controller1_write(data),
-            0x4017 => self.controller2_write(data),
+            0x4016 => self.controller_write(data),
             0x4018..=0x401F => (), // APU and I/O functionality that is normally disabled.
             0x4020..=0xFFFF => self.write_prg_mem(addr, data),
         };
@@ -1564,8 +1706,8 @@ impl CpuBus<'_> {
             0x2000..=0x3FFF => self.read_ppu_register(addr),
             0x4000..=0x4013 | 0x4015 => 0, // TODO: APU
             0x4014 => 0,                   // OAMDMA is write-only
-            0x4016 => self.read_controller1_snapshot(),
-            0x4017 => self.read_controller2_snapshot(),
+            0x4016 => self.read_controller1(),
+            0x4017 => self.read_controller(1),
             0x4018..=0x401F => 0, // APU and I/O functionality that is normally disabled.
             0x4020..=0xFFFF => self.read_prg_mem(addr),
         }
@@ -1576,25 +1718,29 @@ impl CpuBus<'_> {
 mod tests {
     use super::*;
     use crate::Cartridge;
+    use crate::ControllerPort;
+    use crate::InputDevice;
     use crate::Ppu;
     use crate::RAM_SIZE;
     use alloc::vec;
 
     struct MockEmulator {
         cpu: Cpu,
-        controller1: u8,
-        controller2: u8,
-        controller1_state: bool,
-        controller2_state: bool,
-        controller1_snapshot: u8,
-        controller2_snapshot: u8,
+        controller_ports: [ControllerPort; 2],
         ram: [u8; RAM_SIZE as usize],
+        dma_stall_cycles: u16,
+        elapsed_cycles: u64,
         cartridge: Cartridge,
         ppu: Ppu,
         name_tables: [u8; 1024 * 4],
     }
 
     fn mock_emu(prgm: &[u8]) -> MockEmulator {
+        mock_emu_with_vectors(prgm, |_| {})
+    }
+
+    /// `set_vectors` gets the PRG bank holding the vectors, from $C000
+    fn mock_emu_with_vectors(prgm: &[u8], set_vectors: impl FnOnce(&mut [u8])) -> MockEmulator {
         let mut rom = vec![0x00; 65552];
 
         // Dummy header
@@ -1614,18 +1760,16 @@ mod tests {
         // Write PC start to point on $4020
         rom[16 + 0x7FFC] = 0x20;
         rom[16 + 0x7FFD] = 0x40;
+        set_vectors(&mut rom[16 + 0x4000..16 + 0x8000]);
 
         let mut emu = MockEmulator {
             cpu: Default::default(),
-            controller1: 0,
-            controller2: 0,
-            controller1_state: false,
-            controller2_state: false,
-            controller1_snapshot: 0,
-            controller2_snapshot: 0,
+            controller_ports: Default::default(),
             cartridge: Cartridge::load(&rom, None).unwrap(),
 
             ram: [0u8; RAM_SIZE as usize],
+            dma_stall_cycles: 0,
+            elapsed_cycles: 0,
             ppu: Ppu::default(),
             name_tables: [0u8; 1024 * 4],
         };
@@ -1648,6 +1792,42 @@ mod tests {
         }
     }
 
+    #[test]
+    fn controllers_are_independent() {
+        let mut emu = mock_emu(&[]);
+        emu.controller_ports[0].set_buttons(0b1010_0000);
+        emu.controller_ports[1].set_buttons(0b1100_0001);
+        let mut bus = borrow_cpu_bus!(emu);
+
+        // A single strobe on $4016 latches both controllers
+        bus.write(0x4016, 1);
+        bus.write(0x4016, 0);
+
+        let mut reports = [0u8; 2];
+        for _ in 0..8 {
+            reports[0] = (reports[0] << 1) | bus.read(0x4016);
+            reports[1] = (reports[1] << 1) | bus.read(0x4017);
+        }
+        assert_eq!(reports, [0b1010_0000, 0b1100_0001]);
+
+        // $4017 writes go to the APU, they don't strobe controller 2
+        bus.write(0x4017, 1);
+        assert_eq!(bus.read(0x4017), 0);
+    }
+
+    #[test]
+    fn disconnected_controller_reads_open_bus() {
+        let mut emu = mock_emu(&[]);
+        emu.controller_ports[1].set_device(InputDevice::Disconnected);
+        emu.controller_ports[1].set_buttons(0xFF);
+        let mut bus = borrow_cpu_bus!(emu);
+
+        bus.write(0x4016, 1);
+        bus.write(0x4016, 0);
+        assert_eq!(bus.read(0x4016), 0);
+        assert_eq!(bus.read(0x4017), 0x40);
+    }
+
     #[test]
     fn test_0xa9_lda_immediate_load_data() {
         let mut emu = mock_emu(&[0xA9, 0x05]);
@@ -1688,4 +1868,177 @@ mod tests {
         execute_n(&mut emu, 2);
         assert_eq!(emu.cpu.a, 0x55);
     }
+
+    /// Counts the bits a controller port has left to report
+    fn remaining_bits(emu: &mut MockEmulator, addr: u16) -> u8 {
+        let mut bus = borrow_cpu_bus!(emu);
+        (0..8).map(|_| bus.read(addr)).sum()
+    }
+
+    #[test]
+    fn famicom_microphone() {
+        let mut emu = mock_emu(&[]);
+        emu.controller_ports[1].set_microphone(true);
+        let mut bus = borrow_cpu_bus!(emu);
+        assert_eq!(bus.read(0x4016), 0);
+
+        emu.controller_ports[1].set_device(InputDevice::FamicomController);
+        let mut bus = borrow_cpu_bus!(emu);
+        assert_eq!(bus.read(0x4016), 0x04);
+        assert_eq!(bus.read(0x4017), 0);
+    }
+
+    #[test]
+    fn indexed_dummy_reads() {
+        // LDA $40F0,X, LDX #$00, STA $4017,X
+        let mut emu = mock_emu(&[0xBD, 0xF0, 0x40, 0xA2, 0x00, 0x9D, 0x17, 0x40]);
+        emu.cpu.x = 0x26;
+        emu.controller_ports[0].set_buttons(0xFF);
+        emu.controller_ports[1].set_buttons(0xFF);
+        let mut bus = borrow_cpu_bus!(emu);
+        bus.write(0x4016, 1);
+        bus.write(0x4016, 0);
+
+        // Page crossed: $4016 is read before the high byte is fixed to $41
+        execute_n(&mut emu, 2);
+        assert_eq!(remaining_bits(&mut emu, 0x4016), 7);
+
+        // Indexed writes read their target even when the page isn't crossed
+        execute_n(&mut emu, 2);
+        assert_eq!(remaining_bits(&mut emu, 0x4017), 7);
+    }
+
+    #[test]
+    fn decimal_flag_is_ignored() {
+        // SED, CLC, LDA #$09, ADC #$01, SEC, LDA #$10, SBC #$01, PHP, PLA
+        let mut emu = mock_emu(&[
+            0xF8, 0x18, 0xA9, 0x09, 0x69, 0x01, 0x38, 0xA9, 0x10, 0xE9, 0x01, 0x08, 0x68,
+        ]);
+        execute_n(&mut emu, 5);
+        assert_eq!(emu.cpu.a, 0x0A); // 0x10 in decimal mode
+        execute_n(&mut emu, 3);
+        assert_eq!(emu.cpu.a, 0x0F); // 0x09 in decimal mode
+        execute_n(&mut emu, 2);
+        assert_eq!(emu.cpu.a & StatusRegister::D.bits, StatusRegister::D.bits);
+    }
+
+    #[test]
+    fn oam_dma_stalls_cpu() {
+        let mut emu = mock_emu(&[0xA9, 0x02, 0x8D, 0x14, 0x40]);
+        execute_n(&mut emu, 2);
+        assert_eq!(emu.dma_stall_cycles, 0);
+        execute_n(&mut emu, 1);
+        assert_eq!(emu.dma_stall_cycles, 513);
+
+        // One more alignment cycle when the transfer starts on an odd CPU cycle
+        let mut emu = mock_emu(&[0xA9, 0x02, 0x8D, 0x14, 0x40]);
+        emu.elapsed_cycles = 1;
+        execute_n(&mut emu, 3);
+        assert_eq!(emu.dma_stall_cycles, 514);
+    }
+
+    /// Runs until the CPU enters the IRQ handler with the IRQ line held, returns the
+    /// address pushed on the stack
+    fn run_until_irq(emu: &mut MockEmulator) -> u16 {
+        let mut bus = borrow_cpu_bus!(emu);
+        for _ in 0..100 {
+            if emu.cpu.is_polling_interrupts() {
+                emu.cpu.poll_interrupts(false, true);
+            }
+            emu.cpu.clock(&mut bus);
+            if emu.cpu.pc == 0x0000 {
+                // IRQ vector points to $0000 in the mock ROM
+                let lo = bus.read(STACK_BASE + u16::from(emu.cpu.st) + 2);
+                let hi = bus.read(STACK_BASE + u16::from(emu.cpu.st) + 3);
+                return u16::from(lo) | (u16::from(hi) << 8);
+            }
+        }
+        panic!("IRQ was never taken");
+    }
+
+    #[test]
+    fn cli_delays_irq_by_one_instruction() {
+        let mut emu = mock_emu(&[0x58, 0xEA, 0xEA]); // CLI; NOP; NOP
+        assert_eq!(run_until_irq(&mut emu), 0x4022);
+    }
+
+    #[test]
+    fn irq_taken_right_after_sei() {
+        let mut emu = mock_emu(&[0x78, 0xEA, 0xEA]); // SEI; NOP; NOP
+        emu.cpu.cycles = 0; // Skip the reset sequence
+        emu.cpu.status_register.remove(StatusRegister::I);
+        emu.cpu.irq_inhibit = false;
+        assert_eq!(run_until_irq(&mut emu), 0x4021);
+    }
+
+    fn brk_emu() -> MockEmulator {
+        // BRK, then NOPs in the NMI handler at $4030 and the IRQ handler at $4040
+        let mut prgm = [0xEA; 0x21];
+        prgm[0] = 0x00;
+        let mut emu = mock_emu_with_vectors(&prgm, |bank| {
+            bank[0x3FFA..0x3FFC].copy_from_slice(&[0x30, 0x40]);
+            bank[0x3FFE..0x4000].copy_from_slice(&[0x40, 0x40]);
+        });
+        emu.cpu.cycles = 0; // Skip the reset sequence
+        emu
+    }
+
+    #[test]
+    fn nmi_hijacks_brk() {
+        let mut emu = brk_emu();
+        let mut bus = borrow_cpu_bus!(emu);
+        emu.cpu.clock(&mut bus);
+        assert!(emu.cpu.is_polling_interrupts());
+        emu.cpu.poll_interrupts(true, false);
+        while emu.cpu.cycles > 0 {
+            emu.cpu.clock(&mut bus);
+        }
+        assert_eq!(emu.cpu.pc, 0x4030);
+        // The status pushed by BRK is kept, with B set
+        let status = bus.read(STACK_BASE + u16::from(emu.cpu.st) + 1);
+        assert_eq!(status & StatusRegister::B.bits, StatusRegister::B.bits);
+
+        // The NMI is not taken a second time
+        emu.cpu.clock(&mut bus);
+        assert_eq!(emu.cpu.pc, 0x4031);
+    }
+
+    #[test]
+    fn late_nmi_runs_after_brk() {
+        let mut emu = brk_emu();
+        let mut bus = borrow_cpu_bus!(emu);
+        emu.cpu.clock(&mut bus);
+        while emu.cpu.cycles > 1 {
+            assert_eq!(emu.cpu.is_polling_interrupts(), emu.cpu.cycles >= 4);
+            emu.cpu.clock(&mut bus);
+        }
+        emu.cpu.poll_interrupts(true, false);
+        emu.cpu.clock(&mut bus);
+        assert_eq!(emu.cpu.pc, 0x4040);
+
+        // The NMI pushes the IRQ handler address, with B clear
+        emu.cpu.clock(&mut bus);
+        assert_eq!(emu.cpu.pc, 0x4030);
+        let status = bus.read(STACK_BASE + u16::from(emu.cpu.st) + 1);
+        assert_eq!(status & StatusRegister::B.bits, 0);
+        assert_eq!(bus.read(STACK_BASE + u16::from(emu.cpu.st) + 2), 0x40);
+    }
+
+    #[test]
+    fn power_on_state() {
+        let emu = mock_emu(&[]);
+        assert_eq!(emu.cpu.st, 0xFD);
+        assert!(emu.cpu.status_register.contains(StatusRegister::I));
+        assert_eq!((emu.cpu.a, emu.cpu.x, emu.cpu.y), (0, 0, 0));
+    }
+
+    #[test]
+    fn reset_preserves_registers() {
+        let mut emu = mock_emu(&[0xA9, 0x05, 0x58]); // LDA #$05; CLI
+        execute_n(&mut emu, 2);
+        emu.cpu.reset(&mut borrow_cpu_bus!(emu));
+        assert_eq!(emu.cpu.st, 0xFA);
+        assert_eq!(emu.cpu.a, 0x05);
+        assert!(emu.cpu.status_register.contains(StatusRegister::I));
+    }
 }
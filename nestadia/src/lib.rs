@@ -6,31 +6,89 @@ extern crate alloc;
 mod bus;
 
 mod cartridge;
+mod controller;
 mod cpu;
 mod ppu;
+mod state;
+#[cfg(feature = "debugger")]
+mod trace;
 
-pub use cartridge::RomParserError;
+#[cfg(feature = "debugger")]
+pub use cpu::disassembler::{AddressingMode, Instruction};
+#[cfg(feature = "debugger")]
+pub use cpu::StatusFlags;
+
+pub use cartridge::{detect_format, CartridgeInfo, Mirroring, Region, RomFormat, RomParserError};
+pub use controller::{ControllerState, InputDevice};
 pub use cpu::Cpu;
-pub use ppu::Ppu;
+pub use ppu::{
+    Accuracy, BufferSizeError, ColorAdjustment, ColorCorrection, CroppedFrame, FrameDiff, Overscan,
+    Ppu, PpuFrame, RGBA_FRAME_SIZE, RGB_VALUE_TABLE,
+};
+#[cfg(feature = "debugger")]
+pub use ppu::{PpuStatusFlags, PpuWriteCallback, Sprite};
+pub use state::StateError;
+#[cfg(feature = "debugger")]
+pub use trace::{FrameEvent, FrameEventKind, TraceEntry};
+
+#[cfg(feature = "debugger")]
+use alloc::boxed::Box;
 
 use crate::cartridge::Cartridge;
-use crate::ppu::PpuFrame;
+use crate::controller::ControllerPort;
+#[cfg(feature = "debugger")]
+use crate::trace::{FrameEvents, TraceHistory};
 
 pub const RAM_SIZE: u16 = 0x0800;
 
+/// Called when the mapper asserts its IRQ line, with the PPU scanline and cycle.
+/// It is called even if the CPU ignores the IRQ because of the I flag.
+#[cfg(feature = "debugger")]
+pub type MapperIrqCallback = Box<dyn FnMut(i16, u16) + Send>;
+
+/// Called when the CPU writes a watched RAM address, with the address, old and new values
+#[cfg(feature = "debugger")]
+pub type RamWriteCallback = Box<dyn FnMut(u16, u8, u8) + Send>;
+
+#[cfg(feature = "debugger")]
+type RamWatch = (u16, RamWriteCallback);
+
+/// Number of PPU clocks per CPU clock on NTSC consoles
+pub const DEFAULT_CLOCK_DIVIDER: u8 = 3;
+
+/// CPU clock frequency of NTSC consoles, in Hz
+pub const NTSC_CPU_FREQUENCY: u32 = 1_789_773;
+
+/// CPU clock frequency of PAL consoles, in Hz
+pub const PAL_CPU_FREQUENCY: u32 = 1_662_607;
+
+/// Frames `Emulator::step_over` runs before giving up on a subroutine that doesn't return
+#[cfg(feature = "debugger")]
+pub const STEP_OVER_FRAME_LIMIT: u64 = 60;
+
+/// Frames completed by the PPU, see `Emulator::frame_stats`.
+/// Frames run by `clock_no_render` are neither rendered nor skipped.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameStats {
+    /// Frames rendered by `clock` and the methods built on it
+    pub rendered: u64,
+    /// Frames run without rendering because of the frame skip
+    pub skipped: u64,
+    /// Frames currently skipped after each rendered frame, see `Emulator::set_frame_skip`
+    pub skip_rate: u8,
+}
+
 pub struct Emulator {
     // Cartridge is shared by CPU (PRG) and PPU (CHR)
     cartridge: Cartridge,
 
     // == CPU == //
     cpu: Cpu,
-    controller1: u8,
-    controller2: u8,
-    controller1_state: bool,
-    controller2_state: bool,
-    controller1_snapshot: u8,
-    controller2_snapshot: u8,
+    controller_ports: [ControllerPort; 2],
+    controller1_held: ControllerState,
+    controller1_hold_frames: u16,
     ram: [u8; RAM_SIZE as usize],
+    dma_stall_cycles: u16,
 
     // == PPU == //
     ppu: Ppu,
@@ -38,6 +96,28 @@ pub struct Emulator {
 
     // Emulator internal state
     clock_count: u8,
+    clock_divider: u8, // PPU clocks per CPU clock
+    overscan: Overscan,
+    frame_number: u64,
+    frame_skip: u8,     // Frames skipped after each rendered frame
+    frames_to_skip: u8, // Frames left to skip before rendering again
+    frames_rendered: u64,
+    frames_skipped: u64,
+    lag_frame: bool,
+    elapsed_cycles: u64,
+    completed_frame: PpuFrame, // Copy of the last frame completed by `run_for_duration`
+    duration_remainder: u64,   // Fraction of a CPU cycle left by `run_for_duration`, in µs·Hz
+
+    #[cfg(feature = "debugger")]
+    mapper_irq_callback: Option<MapperIrqCallback>,
+    #[cfg(feature = "debugger")]
+    mapper_irq_line: bool, // Mapper IRQ state at the last CPU clock, to report assertions
+    #[cfg(feature = "debugger")]
+    trace_history: Option<TraceHistory>,
+    #[cfg(feature = "debugger")]
+    frame_events: Option<FrameEvents>,
+    #[cfg(feature = "debugger")]
+    ram_watches: alloc::vec::Vec<RamWatch>,
 }
 
 impl Emulator {
@@ -46,75 +126,538 @@ impl Emulator {
             cartridge: Cartridge::load(rom, save_data)?,
 
             cpu: Default::default(),
-            controller1: 0,
-            controller2: 0,
-            controller1_state: false,
-            controller2_state: false,
-            controller1_snapshot: 0,
-            controller2_snapshot: 0,
+            controller_ports: Default::default(),
+            controller1_held: ControllerState::empty(),
+            controller1_hold_frames: 0,
             ram: [0u8; RAM_SIZE as usize],
+            dma_stall_cycles: 0,
 
             ppu: Ppu::new(),
             name_tables: [0u8; 1024 * 4],
 
             clock_count: 0,
+            clock_divider: DEFAULT_CLOCK_DIVIDER,
+            overscan: Overscan::default(),
+            frame_number: 0,
+            frame_skip: 0,
+            frames_to_skip: 0,
+            frames_rendered: 0,
+            frames_skipped: 0,
+            lag_frame: false,
+            elapsed_cycles: 0,
+            completed_frame: PpuFrame::default(),
+            duration_remainder: 0,
+
+            #[cfg(feature = "debugger")]
+            mapper_irq_callback: None,
+            #[cfg(feature = "debugger")]
+            mapper_irq_line: false,
+            #[cfg(feature = "debugger")]
+            trace_history: None,
+            #[cfg(feature = "debugger")]
+            frame_events: None,
+            #[cfg(feature = "debugger")]
+            ram_watches: alloc::vec::Vec::new(),
         };
 
-        emulator.reset();
+        emulator.reset_console();
 
         Ok(emulator)
     }
 
-    pub fn clock(&mut self) -> Option<&PpuFrame> {
-        // Make PPU clock first
+    /// Clocks the PPU once, and the CPU when its turn comes.
+    /// Returns the frame, with the overscan cropped, when the PPU completes one.
+    pub fn clock(&mut self) -> Option<CroppedFrame<'_>> {
+        // Make PPU clock first, frames skipped by the frame skip are not rendered
+        let skipping = self.frames_to_skip > 0;
         let mut ppu_bus = borrow_ppu_bus!(self);
-        self.ppu.clock(&mut ppu_bus);
+        if skipping {
+            self.ppu.clock_no_render(&mut ppu_bus);
+        } else {
+            self.ppu.clock(&mut ppu_bus);
+        }
+
+        self.clock_cpu();
+
+        // returns PPU frame if any
+        if !self.frame_completed() {
+            None
+        } else if skipping {
+            self.frames_to_skip -= 1;
+            self.frames_skipped += 1;
+            None
+        } else {
+            self.frames_to_skip = self.frame_skip;
+            self.frames_rendered += 1;
+            Some(CroppedFrame::new(self.ppu.frame(), self.overscan))
+        }
+    }
 
-        // CPU clock is 3 times slower
-        if self.clock_count % 3 == 0 {
+    /// Same as `clock`, but the PPU only keeps its timing, flags and interrupts
+    /// without rendering any pixel. Returns whether a frame was completed.
+    pub fn clock_no_render(&mut self) -> bool {
+        let mut ppu_bus = borrow_ppu_bus!(self);
+        self.ppu.clock_no_render(&mut ppu_bus);
+
+        self.clock_cpu();
+
+        self.frame_completed()
+    }
+
+    fn clock_cpu(&mut self) {
+        // CPU clock is 3 times slower, unless the divider was changed
+        if self.clock_count.is_multiple_of(self.clock_divider) {
             self.clock_count = 0;
+            self.elapsed_cycles += 1;
+            self.cartridge.cpu_clock();
 
-            if self.cpu.cycles == 0 && self.ppu.take_vblank_nmi_set_state() {
-                // NMI interrupt
-                let mut cpu_bus = borrow_cpu_bus!(self);
-                self.cpu.nmi(&mut cpu_bus);
-                self.cpu.clock(&mut cpu_bus);
-            } else if self.cpu.cycles == 0 && self.cartridge.take_irq_set_state() {
-                // IRQ interrupt
-                let mut cpu_bus = borrow_cpu_bus!(self);
-                self.cpu.irq(&mut cpu_bus);
-                self.cpu.clock(&mut cpu_bus);
+            #[cfg(feature = "debugger")]
+            self.watch_mapper_irq();
+
+            if self.cpu.cycles == 0 && self.dma_stall_cycles > 0 {
+                // CPU is suspended during OAM DMA
+                self.dma_stall_cycles -= 1;
             } else {
+                if self.cpu.is_polling_interrupts() {
+                    // NMI and IRQ interrupts, taken once the current instruction completes
+                    let nmi = self.ppu.take_vblank_nmi_set_state();
+                    let irq = !self.cpu.irq_inhibited() && self.cartridge.take_irq_set_state();
+
+                    #[cfg(feature = "debugger")]
+                    if nmi {
+                        self.record_event(FrameEventKind::Nmi);
+                    }
+                    #[cfg(feature = "debugger")]
+                    if irq {
+                        self.record_event(FrameEventKind::MapperIrq);
+                    }
+
+                    self.cpu.poll_interrupts(nmi, irq);
+                }
+
+                #[cfg(feature = "debugger")]
+                if self.cpu.cycles == 0 && !self.cpu.interrupt_pending() {
+                    self.record_trace();
+                }
+
+                #[cfg(feature = "debugger")]
+                let dma_started = self.dma_stall_cycles == 0;
+
                 let mut cpu_bus = borrow_cpu_bus!(self);
+                #[cfg(feature = "debugger")]
+                cpu_bus.set_ram_watches(&mut self.ram_watches);
                 self.cpu.clock(&mut cpu_bus);
+
+                #[cfg(feature = "debugger")]
+                if dma_started && self.dma_stall_cycles > 0 {
+                    self.record_event(FrameEventKind::OamDma);
+                }
             }
         }
 
         self.clock_count = self.clock_count.wrapping_add(1);
+    }
 
-        // returns PPU frame if any
-        self.ppu.ready_frame()
+    fn frame_completed(&mut self) -> bool {
+        let completed = self.ppu.ready_frame().is_some();
+        if completed {
+            self.frame_number += 1;
+            self.tick_held_buttons();
+
+            // Both flags must be cleared for the next frame
+            let polled = self
+                .controller_ports
+                .iter_mut()
+                .fold(false, |polled, port| port.take_polled() | polled);
+            self.lag_frame = !polled;
+
+            #[cfg(feature = "debugger")]
+            if let Some(events) = &mut self.frame_events {
+                events.end_frame();
+            }
+        }
+        completed
+    }
+
+    /// Runs the emulator until the PPU completes a frame, and returns it with the overscan cropped
+    pub fn clock_frame(&mut self) -> CroppedFrame<'_> {
+        while self.clock().is_none() {}
+        self.frame()
+    }
+
+    /// Runs one frame per entry of `inputs`, with controller 1 set to the entry during
+    /// the frame. Frames are not captured: only the last one can be read, with `frame`.
+    /// Controller 1 keeps the last input afterwards.
+    pub fn run_input_sequence(&mut self, inputs: &[u8]) {
+        for &input in inputs {
+            self.set_controller1(input);
+            self.clock_frame();
+        }
+    }
+
+    /// Runs the emulator until the PPU completes a frame, and writes it as RGBA into `buffer`,
+    /// with the overscan area cropped out. The buffer length is checked before running, it
+    /// must be 4 bytes per pixel of `frame_size`.
+    pub fn clock_frame_into(&mut self, buffer: &mut [u8]) -> Result<(), BufferSizeError> {
+        let (width, height) = self.frame_size();
+        if buffer.len() != width * height * 4 {
+            return Err(BufferSizeError {
+                expected: width * height * 4,
+                actual: buffer.len(),
+            });
+        }
+
+        self.clock_frame();
+        self.frame()
+            .to_rgba_with_palette(self.ppu.rgb_palette(), buffer)
+    }
+
+    /// Sets the correction applied to the palette when frames are converted to RGBA
+    /// by `clock_frame_into`. Defaults to `ColorCorrection::None`.
+    pub fn set_color_correction(&mut self, correction: ColorCorrection) {
+        self.ppu.set_color_correction(correction);
+    }
+
+    /// Palette used by `clock_frame_into`, with the color correction applied
+    pub fn rgb_palette(&self) -> &[[u8; 3]; 64] {
+        self.ppu.rgb_palette()
+    }
+
+    /// Runs the CPU cycles lasting `micros` microseconds at the frequency of the region,
+    /// and returns the last completed frame with the overscan cropped. The fractions of cycles left are carried to
+    /// the next call, so calling it with the time elapsed since the last call doesn't drift.
+    pub fn run_for_duration(&mut self, micros: u64) -> CroppedFrame<'_> {
+        use core::convert::TryFrom;
+
+        // In µs·Hz, which doesn't fit in a u64 for long durations
+        let total = u128::from(micros) * u128::from(self.cpu_frequency())
+            + u128::from(self.duration_remainder);
+        self.duration_remainder = (total % 1_000_000) as u64;
+
+        let cycles = u64::try_from(total / 1_000_000).unwrap_or(u64::MAX);
+        let target = self.elapsed_cycles.saturating_add(cycles);
+        while self.elapsed_cycles < target {
+            if self.clock().is_some() {
+                // The PPU draws the next frame over the completed one
+                self.completed_frame.clone_from(self.ppu.frame());
+            }
+        }
+
+        CroppedFrame::new(&self.completed_frame, self.overscan)
+    }
+
+    /// Runs frames until the game enables background or sprite rendering, to skip the
+    /// frames drawn while it initializes. Stops after `max_frames` frames at most.
+    /// Returns the number of frames run.
+    pub fn run_until_stable(&mut self, max_frames: u32) -> u32 {
+        let mut frames = 0;
+        while frames < max_frames && !self.ppu.rendering_enabled() {
+            self.clock_frame();
+            frames += 1;
+        }
+        frames
+    }
+
+    /// Whether the game enabled background or sprite rendering in PPUMASK
+    pub fn rendering_enabled(&self) -> bool {
+        self.ppu.rendering_enabled()
+    }
+
+    /// Universal background color, as an index in `RGB_VALUE_TABLE`. It shows through
+    /// transparent pixels, so it blends in as a border color.
+    pub fn backdrop_color(&self) -> u8 {
+        self.ppu.backdrop_color()
+    }
+
+    /// Last frame rendered by the PPU with the overscan area cropped out.
+    /// Its size is given by `frame_size`.
+    pub fn frame(&self) -> CroppedFrame<'_> {
+        CroppedFrame::new(self.ppu.frame(), self.overscan)
+    }
+
+    /// Last frame rendered by the PPU, all 256x240 pixels including the overscan area
+    pub fn full_frame(&self) -> &PpuFrame {
+        self.ppu.frame()
+    }
+
+    /// Sets how many pixels are hidden on each edge of the frames output by the emulator,
+    /// `full_frame` still gives the whole frame
+    pub fn set_overscan(&mut self, top: u8, bottom: u8, left: u8, right: u8) {
+        self.overscan = Overscan {
+            top,
+            bottom,
+            left,
+            right,
+        };
+    }
+
+    pub fn overscan(&self) -> Overscan {
+        self.overscan
+    }
+
+    /// Width and height of the frames output by the emulator, once the overscan is cropped
+    pub fn frame_size(&self) -> (usize, usize) {
+        (self.overscan.width(), self.overscan.height())
+    }
+
+    /// Sets the number of PPU clocks per CPU clock (3 on hardware), for overclocking
+    /// or underclocking experiments. A divider of 0 is treated as 1.
+    pub fn set_clock_divider(&mut self, divider: u8) {
+        self.clock_divider = divider.max(1);
+        self.clock_count = 0;
+    }
+
+    pub fn clock_divider(&self) -> u8 {
+        self.clock_divider
+    }
+
+    /// Position of the next `clock` within the group of PPU clocks of one CPU clock,
+    /// from 0 to `clock_divider() - 1`. At 0, the next `clock` also ticks the CPU.
+    pub fn clock_phase(&self) -> u8 {
+        self.clock_count % self.clock_divider
+    }
+
+    /// Selects the PPU rendering mode, can be changed at any time
+    pub fn set_accuracy(&mut self, accuracy: Accuracy) {
+        self.ppu.set_accuracy(accuracy);
+    }
+
+    pub fn accuracy(&self) -> Accuracy {
+        self.ppu.accuracy()
     }
 
     pub fn set_controller1(&mut self, state: u8) {
-        self.controller1 = state;
+        self.controller_ports[0].set_buttons(state);
+    }
+
+    /// Holds `buttons` on controller 1 for the next `frames` completed frames, then
+    /// releases them. Other buttons are left untouched.
+    pub fn press_controller1(&mut self, buttons: ControllerState, frames: u16) {
+        self.release_held_buttons();
+        if frames > 0 {
+            let port = &mut self.controller_ports[0];
+            port.set_buttons(port.buttons() | buttons.bits());
+            self.controller1_held = buttons;
+            self.controller1_hold_frames = frames;
+        }
+    }
+
+    fn tick_held_buttons(&mut self) {
+        if self.controller1_hold_frames > 0 {
+            self.controller1_hold_frames -= 1;
+            if self.controller1_hold_frames == 0 {
+                self.release_held_buttons();
+            }
+        }
+    }
+
+    fn release_held_buttons(&mut self) {
+        let port = &mut self.controller_ports[0];
+        port.set_buttons(port.buttons() & !self.controller1_held.bits());
+        self.controller1_held = ControllerState::empty();
+        self.controller1_hold_frames = 0;
     }
 
     pub fn set_controller2(&mut self, state: u8) {
-        self.controller2 = state;
+        self.controller_ports[1].set_buttons(state);
+    }
+
+    /// Plugs `device` in controller port 1 ($4016) or 2 ($4017)
+    pub fn set_port_device(&mut self, port: u8, device: InputDevice) {
+        match port {
+            1 | 2 => self.controller_ports[usize::from(port - 1)].set_device(device),
+            _ => log::warn!("Attempted to plug a device in invalid port {}", port),
+        }
+    }
+
+    /// Plugs a standard controller in port 1 or 2, or unplugs the device.
+    /// Reads from a disconnected port return open bus.
+    pub fn set_controller_connected(&mut self, port: u8, connected: bool) {
+        let device = if connected {
+            InputDevice::StandardController
+        } else {
+            InputDevice::Disconnected
+        };
+        self.set_port_device(port, device);
+    }
+
+    /// Wires controllers like a Famicom, where controller 2 has a microphone.
+    /// Disabling it plugs NES controllers back.
+    pub fn set_famicom_mode(&mut self, enabled: bool) {
+        let device = if enabled {
+            InputDevice::FamicomController
+        } else {
+            InputDevice::StandardController
+        };
+        for port in self.controller_ports.iter_mut() {
+            port.set_device(device);
+        }
+    }
+
+    /// Blows in the microphone of controller 2, only heard in Famicom mode
+    pub fn set_microphone(&mut self, active: bool) {
+        self.controller_ports[1].set_microphone(active);
+    }
+
+    /// Device plugged in controller port 1 ($4016) or 2 ($4017)
+    pub fn port_device(&self, port: u8) -> Option<InputDevice> {
+        match port {
+            1 | 2 => Some(self.controller_ports[usize::from(port - 1)].device()),
+            _ => None,
+        }
+    }
+
+    /// Buttons state of controller 1 as the game observes it, in NES button order
+    /// (A, B, Select, Start, Up, Down, Left, Right from MSB to LSB).
+    /// Unlike reads on $4016, this doesn't shift the controller's register.
+    pub fn controller1_report(&self) -> u8 {
+        self.controller_ports[0].report()
+    }
+
+    /// Buttons state of controller 2 as the game observes it, in NES button order
+    /// (A, B, Select, Start, Up, Down, Left, Right from MSB to LSB).
+    /// Unlike reads on $4017, this doesn't shift the controller's register.
+    pub fn controller2_report(&self) -> u8 {
+        self.controller_ports[1].report()
     }
 
+    /// Resets the console like the reset button would.
+    /// The frame counter and the elapsed cycles are preserved.
     pub fn reset(&mut self) {
+        // Power-on and power cycles don't reach the mapper, only the reset button does
+        self.cartridge.reset();
+        self.reset_console();
+    }
+
+    /// Resets the CPU and PPU without telling the mapper
+    fn reset_console(&mut self) {
         let mut cpu_bus = borrow_cpu_bus!(self);
         self.cpu.reset(&mut cpu_bus);
         self.ppu.reset();
         self.clock_count = 0;
+        self.dma_stall_cycles = 0;
+    }
+
+    /// Stable identifier of the loaded ROM: a 64-bit FNV-1a hash of its PRG and CHR ROM.
+    /// The header is not hashed.
+    pub fn rom_hash(&self) -> u64 {
+        self.cartridge.rom_hash()
+    }
+
+    /// Turns the console off and on again. Unlike `reset`, CPU RAM and VRAM are cleared,
+    /// the CPU registers go back to their power-up state, and the frame counter and the
+    /// elapsed cycles restart from 0. The mapper registers go back to their power-up state,
+    /// but PRG RAM is kept, like battery-backed memory would be.
+    pub fn power_cycle(&mut self) {
+        self.cartridge.power_cycle();
+        self.ram.fill(0);
+        self.name_tables.fill(0);
+        self.cpu = Cpu::default();
+        self.frame_number = 0;
+        self.frames_to_skip = 0;
+        self.frames_rendered = 0;
+        self.frames_skipped = 0;
+        self.lag_frame = false;
+        self.elapsed_cycles = 0;
+        self.duration_remainder = 0;
+        // Also clears OAM and palette RAM
+        self.reset_console();
     }
 
+    /// Swaps PRG and CHR ROM for the ones of a rebuilt `rom` and resets the console,
+    /// keeping RAM contents. Fails with `RomParserError::IncompatibleReload` if the
+    /// mapper or memory sizes changed, in which case a new `Emulator` is required.
+    pub fn reload_prg_chr(&mut self, rom: &[u8]) -> Result<(), RomParserError> {
+        self.cartridge.reload_prg_chr(rom)?;
+        self.reset_console();
+        Ok(())
+    }
+
+    /// PRG RAM contents, for cartridges that have some. The slice always covers the
+    /// whole allocated PRG RAM (see `CartridgeInfo::prg_ram_size`), so it can be written
+    /// as is to a save file.
     pub fn get_save_data(&self) -> Option<&[u8]> {
         self.cartridge.get_save_data()
     }
 
+    /// Length of the slice returned by `get_save_data`
+    pub fn save_data_len(&self) -> Option<usize> {
+        self.cartridge.get_save_data().map(<[u8]>::len)
+    }
+
+    pub fn cartridge_info(&self) -> CartridgeInfo {
+        self.cartridge.info()
+    }
+
+    /// CPU clock frequency in Hz for the region of the cartridge
+    pub fn cpu_frequency(&self) -> u32 {
+        match self.cartridge.info().region {
+            Region::Ntsc => NTSC_CPU_FREQUENCY,
+            Region::Pal => PAL_CPU_FREQUENCY,
+        }
+    }
+
+    /// Forces the nametable mirroring regardless of what the cartridge reports.
+    /// `None` restores the cartridge's own mirroring.
+    pub fn set_mirroring_override(&mut self, mirroring: Option<Mirroring>) {
+        self.cartridge.set_mirroring_override(mirroring);
+    }
+
+    /// Nametable mirroring currently in effect, as selected by the mapper or overridden
+    pub fn mirroring(&self) -> Mirroring {
+        self.cartridge.mirroring()
+    }
+
+    /// See `Cartridge::force_prg_bank`
+    #[cfg(feature = "debugger")]
+    pub fn force_prg_bank(&mut self, slot: u8, bank: u16) {
+        self.cartridge.force_prg_bank(slot, bank);
+    }
+
+    /// See `Cartridge::force_chr_bank`
+    #[cfg(feature = "debugger")]
+    pub fn force_chr_bank(&mut self, slot: u8, bank: u16) {
+        self.cartridge.force_chr_bank(slot, bank);
+    }
+
+    #[cfg(feature = "debugger")]
+    pub fn clear_forced_banks(&mut self) {
+        self.cartridge.clear_forced_banks();
+    }
+
+    /// Number of frames completed by the PPU since the emulator was created or power cycled
+    pub fn frame_number(&self) -> u64 {
+        self.frame_number
+    }
+
+    /// Whether the game didn't read the controllers during the last completed frame.
+    /// Inputs given during a lag frame are only seen on a later frame.
+    pub fn was_lag_frame(&self) -> bool {
+        self.lag_frame
+    }
+
+    /// Frames rendered and skipped since the emulator was created or power cycled
+    pub fn frame_stats(&self) -> FrameStats {
+        FrameStats {
+            rendered: self.frames_rendered,
+            skipped: self.frames_skipped,
+            skip_rate: self.frame_skip,
+        }
+    }
+
+    /// Renders one frame out of `rate + 1`. The other frames are run without rendering,
+    /// and `clock` doesn't return them, so `clock_frame` runs `rate + 1` frames.
+    /// 0 disables the frame skip.
+    pub fn set_frame_skip(&mut self, rate: u8) {
+        self.frame_skip = rate;
+        self.frames_to_skip = self.frames_to_skip.min(rate);
+    }
+
+    /// Number of CPU cycles elapsed since the emulator was created or power cycled
+    pub fn elapsed_cycles(&self) -> u64 {
+        self.elapsed_cycles
+    }
+
     #[cfg(feature = "debugger")]
     #[allow(unused_variables)] // FIXME
     pub fn disassemble(
@@ -125,8 +668,763 @@ impl Emulator {
         self.cartridge.disassemble()
     }
 
+    /// Decodes instructions lazily from `start`, reading memory without side effects.
+    /// The iterator never ends, wrapping around the address space.
+    #[cfg(feature = "debugger")]
+    pub fn disassemble_iter(&self, start: u16) -> impl Iterator<Item = (u16, Instruction)> + '_ {
+        let mut addr = start;
+        core::iter::from_fn(move || {
+            let instruction = Instruction::decode([
+                self.peek(addr),
+                self.peek(addr.wrapping_add(1)),
+                self.peek(addr.wrapping_add(2)),
+            ]);
+            let instruction_addr = addr;
+            addr = addr.wrapping_add(instruction.size() as u16);
+            Some((instruction_addr, instruction))
+        })
+    }
+
+    /// Decodes the instruction the CPU will execute next, at its PC.
+    /// If the PC points to memory-mapped registers, they are decoded as 0 (BRK).
+    #[cfg(feature = "debugger")]
+    pub fn current_instruction(&self) -> (u16, Instruction) {
+        let pc = self.cpu.pc;
+        let instruction = Instruction::decode([
+            self.peek(pc),
+            self.peek(pc.wrapping_add(1)),
+            self.peek(pc.wrapping_add(2)),
+        ]);
+        (pc, instruction)
+    }
+
+    /// Starts keeping the last `capacity` executed instructions, see `trace_history`.
+    /// A capacity of 0 disables the history.
+    #[cfg(feature = "debugger")]
+    pub fn enable_trace_history(&mut self, capacity: usize) {
+        self.trace_history = if capacity > 0 {
+            Some(TraceHistory::new(capacity))
+        } else {
+            None
+        };
+    }
+
+    /// Last executed instructions, oldest first. Empty if the history is disabled.
+    #[cfg(feature = "debugger")]
+    pub fn trace_history(&self) -> &[TraceEntry] {
+        match &self.trace_history {
+            Some(history) => history.entries(),
+            None => &[],
+        }
+    }
+
+    #[cfg(feature = "debugger")]
+    fn record_trace(&mut self) {
+        if self.trace_history.is_none() {
+            return;
+        }
+
+        let (pc, instruction) = self.current_instruction();
+        let entry = TraceEntry {
+            pc,
+            instruction,
+            a: self.cpu.a,
+            x: self.cpu.x,
+            y: self.cpu.y,
+            st: self.cpu.st,
+            status: self.cpu.status_register.bits(),
+            cycle: self.elapsed_cycles,
+        };
+
+        if let Some(history) = &mut self.trace_history {
+            history.push(entry);
+        }
+    }
+
+    /// Starts or stops recording interrupts and DMA, see `frame_events`
+    #[cfg(feature = "debugger")]
+    pub fn enable_frame_events(&mut self, enabled: bool) {
+        self.frame_events = if enabled {
+            Some(FrameEvents::default())
+        } else {
+            None
+        };
+    }
+
+    /// Interrupts and DMA of the last completed frame, in order. Frames end when the
+    /// pre-render line starts. Empty if recording is disabled.
+    #[cfg(feature = "debugger")]
+    pub fn frame_events(&self) -> &[FrameEvent] {
+        match &self.frame_events {
+            Some(events) => events.last_frame(),
+            None => &[],
+        }
+    }
+
+    #[cfg(feature = "debugger")]
+    fn record_event(&mut self, kind: FrameEventKind) {
+        if let Some(events) = &mut self.frame_events {
+            events.push(FrameEvent {
+                kind,
+                scanline: self.ppu.scanline(),
+                cycle: self.ppu.cycle(),
+            });
+        }
+    }
+
+    /// Reads CPU memory without any side effect.
+    /// Memory-mapped registers can't be peeked and read as 0.
+    #[cfg(feature = "debugger")]
+    pub fn peek(&self, addr: u16) -> u8 {
+        match addr {
+            0..=0x1FFF => self.ram[(addr & (RAM_SIZE - 1)) as usize],
+            0x4020..=0xFFFF => self.cartridge.read_prg_mem(addr),
+            _ => 0,
+        }
+    }
+
+    /// Stack pointer and the stack page ($0100-$01FF). The stack grows downward:
+    /// the last pushed byte is at `SP + 1`, so return addresses are found above SP.
+    #[cfg(feature = "debugger")]
+    pub fn stack(&self) -> (u8, [u8; 256]) {
+        let mut page = [0u8; 256];
+        page.copy_from_slice(&self.ram[0x0100..0x0200]);
+        (self.cpu.st, page)
+    }
+
+    /// Reads PPU memory (CHR, nametables and palette) with mirroring applied,
+    /// without the side effects of going through $2006/$2007.
+    #[cfg(feature = "debugger")]
+    pub fn vram_read(&mut self, addr: u16) -> u8 {
+        let mut ppu_bus = borrow_ppu_bus!(self);
+        self.ppu.vram_read(&mut ppu_bus, addr)
+    }
+
+    /// Writes PPU memory (CHR, nametables and palette) with mirroring applied,
+    /// without the side effects of going through $2006/$2007.
+    #[cfg(feature = "debugger")]
+    pub fn vram_write(&mut self, addr: u16, data: u8) {
+        let mut ppu_bus = borrow_ppu_bus!(self);
+        self.ppu.vram_write(&mut ppu_bus, addr, data);
+    }
+
+    /// Registers a callback fired on every PPU register write, see `PpuWriteCallback`
+    #[cfg(feature = "debugger")]
+    pub fn set_ppu_write_callback(&mut self, callback: PpuWriteCallback) {
+        self.ppu.set_write_callback(Some(callback));
+    }
+
+    #[cfg(feature = "debugger")]
+    pub fn clear_ppu_write_callback(&mut self) {
+        self.ppu.set_write_callback(None);
+    }
+
+    /// Registers a callback fired when the CPU writes `addr` in RAM, without halting.
+    /// Mirrors of the address are watched too, the callback gets the address in $0000-$07FF.
+    #[cfg(feature = "debugger")]
+    pub fn watch_ram_write(&mut self, addr: u16, callback: RamWriteCallback) {
+        self.ram_watches.push((addr & (RAM_SIZE - 1), callback));
+    }
+
+    #[cfg(feature = "debugger")]
+    pub fn clear_ram_watches(&mut self) {
+        self.ram_watches.clear();
+    }
+
+    /// Registers a callback fired every time the mapper asserts its IRQ, see `MapperIrqCallback`
+    #[cfg(feature = "debugger")]
+    pub fn set_mapper_irq_callback(&mut self, callback: MapperIrqCallback) {
+        self.mapper_irq_callback = Some(callback);
+    }
+
+    #[cfg(feature = "debugger")]
+    pub fn clear_mapper_irq_callback(&mut self) {
+        self.mapper_irq_callback = None;
+    }
+
+    #[cfg(feature = "debugger")]
+    fn watch_mapper_irq(&mut self) {
+        let asserted = self.cartridge.irq_state();
+        if asserted && !self.mapper_irq_line {
+            if let Some(callback) = &mut self.mapper_irq_callback {
+                callback(self.ppu.scanline(), self.ppu.cycle());
+            }
+        }
+        self.mapper_irq_line = asserted;
+    }
+
+    /// Shows the leftmost 8 pixels of background and sprites even when the game hides them
+    #[cfg(feature = "debugger")]
+    pub fn force_show_left_edge(&mut self, force: bool) {
+        self.ppu.set_force_show_left_edge(force);
+    }
+
+    /// Last value written on PPUCTRL ($2000): NMI enable, sprite size, pattern tables,
+    /// VRAM increment and base nametable
+    #[cfg(feature = "debugger")]
+    pub fn ppu_ctrl(&self) -> u8 {
+        self.ppu.ctrl()
+    }
+
+    /// Last value written on PPUMASK ($2001): color emphasis, rendering and left edge
+    /// clipping of background and sprites, grayscale
+    #[cfg(feature = "debugger")]
+    pub fn ppu_mask(&self) -> u8 {
+        self.ppu.mask()
+    }
+
+    /// PPU status flags, read without clearing vblank like a $2002 read would
+    #[cfg(feature = "debugger")]
+    pub fn ppu_status_flags(&self) -> PpuStatusFlags {
+        self.ppu.status_flags()
+    }
+
+    /// See `Ppu::sprite`
+    #[cfg(feature = "debugger")]
+    pub fn sprite(&self, index: u8) -> Sprite {
+        self.ppu.sprite(index)
+    }
+
+    /// See `Ppu::sprites_on_scanline`
+    #[cfg(feature = "debugger")]
+    pub fn sprites_on_scanline(&self, line: i16) -> u8 {
+        self.ppu.sprites_on_scanline(line)
+    }
+
     #[cfg(feature = "debugger")]
     pub fn cpu(&self) -> &Cpu {
         &self.cpu
     }
+
+    /// Moves the program counter, the next instruction is fetched from `addr`.
+    /// Used to start test ROMs like nestest at their automated entry point.
+    #[cfg(feature = "debugger")]
+    pub fn set_pc(&mut self, addr: u16) {
+        self.cpu.pc = addr;
+        self.cpu.cycles = 0;
+    }
+
+    /// Whether the CPU is currently suspended by an OAM DMA transfer
+    #[cfg(feature = "debugger")]
+    pub fn cpu_is_stalled(&self) -> bool {
+        self.dma_stall_cycles > 0
+    }
+
+    /// Number of CPU cycles left before the DMA transfer releases the CPU
+    #[cfg(feature = "debugger")]
+    pub fn cpu_stall_cycles(&self) -> u16 {
+        self.dma_stall_cycles
+    }
+
+    /// Runs the emulator until the CPU has executed exactly one instruction
+    /// (or entered an interrupt handler) and is ready to fetch the next one.
+    #[cfg(feature = "debugger")]
+    pub fn step(&mut self) {
+        // Wait for the CPU tick that starts a new operation
+        loop {
+            let starts_operation =
+                self.clock_phase() == 0 && self.cpu.cycles == 0 && self.dma_stall_cycles == 0;
+            self.clock();
+            if starts_operation {
+                break;
+            }
+        }
+
+        // Let the operation complete
+        while self.cpu.cycles > 0 {
+            self.clock();
+        }
+    }
+
+    /// Clocks the PPU alone for one cycle, leaving the CPU where it is.
+    /// Returns the new scanline and cycle of the PPU.
+    #[cfg(feature = "debugger")]
+    pub fn step_ppu(&mut self) -> (i16, u16) {
+        let mut ppu_bus = borrow_ppu_bus!(self);
+        self.ppu.clock(&mut ppu_bus);
+        self.frame_completed();
+
+        (self.ppu.scanline(), self.ppu.cycle())
+    }
+
+    /// Steps over the current instruction. If it is a JSR, runs until the subroutine
+    /// returns, otherwise behaves like `step`.
+    /// Interrupts taken while the subroutine runs are executed transparently.
+    /// Returns false if the subroutine didn't return within `STEP_OVER_FRAME_LIMIT` frames,
+    /// the CPU is then left wherever it was.
+    #[cfg(feature = "debugger")]
+    pub fn step_over(&mut self) -> bool {
+        const JSR_OPCODE: u8 = 0x20;
+
+        let call_pc = self.cpu.pc;
+        let call_st = self.cpu.st;
+
+        if self.peek(call_pc) != JSR_OPCODE {
+            self.step();
+            return true;
+        }
+
+        let return_pc = call_pc.wrapping_add(3);
+        let start_frame = self.frame_number;
+        loop {
+            self.step();
+            if self.cpu.pc == return_pc && self.cpu.st == call_st {
+                return true;
+            }
+            if self.frame_number - start_frame >= STEP_OVER_FRAME_LIMIT {
+                return false;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::vec;
+
+    fn nrom() -> alloc::vec::Vec<u8> {
+        let mut rom = vec![0u8; 16 + 0x4000 + 0x2000];
+        rom[..6].copy_from_slice(&[0x4E, 0x45, 0x53, 0x1A, 0x01, 0x01]);
+        rom
+    }
+
+    #[test]
+    fn power_cycle_clears_memories() {
+        let mut emu = Emulator::new(&nrom(), None).unwrap();
+        emu.ram[0x0123] = 0x55;
+        emu.name_tables[0x0456] = 0xAA;
+        emu.clock_frame();
+
+        // PPUADDR is ignored during the warmup, which ended with the first frame
+        let mut ppu_bus = borrow_ppu_bus!(emu);
+        emu.ppu.write_oam_dma(&[0x77; 256]);
+        emu.ppu.write(&mut ppu_bus, 0x2006, 0x3F);
+        emu.ppu.write(&mut ppu_bus, 0x2006, 0x00);
+        emu.ppu.write(&mut ppu_bus, 0x2007, 0x2A);
+        assert_eq!(emu.ppu.read(&mut ppu_bus, 0x2004), 0x77);
+        assert_eq!(emu.backdrop_color(), 0x2A);
+
+        emu.power_cycle();
+        assert!(emu.ram.iter().all(|&b| b == 0));
+        assert!(emu.name_tables.iter().all(|&b| b == 0));
+        assert_eq!(emu.cpu.st, 0xFD);
+        assert_eq!(emu.backdrop_color(), 0x00);
+        let mut ppu_bus = borrow_ppu_bus!(emu);
+        assert_eq!(emu.ppu.read(&mut ppu_bus, 0x2004), 0x00);
+        assert_eq!(emu.frame_number(), 0);
+        assert_eq!(emu.frame_stats(), FrameStats::default());
+        assert_eq!(emu.elapsed_cycles(), 0);
+    }
+
+    #[cfg(feature = "debugger")]
+    #[test]
+    fn trace_history() {
+        let mut rom = nrom();
+        // NOP, NOP, JMP $8000
+        rom[16..21].copy_from_slice(&[0xEA, 0xEA, 0x4C, 0x00, 0x80]);
+        rom[16 + 0x3FFC..16 + 0x3FFE].copy_from_slice(&[0x00, 0x80]);
+
+        let mut emu = Emulator::new(&rom, None).unwrap();
+        emu.enable_trace_history(4);
+        for _ in 0..5 {
+            emu.step();
+        }
+
+        let pcs: alloc::vec::Vec<u16> = emu.trace_history().iter().map(|e| e.pc).collect();
+        assert_eq!(pcs, [0x8001, 0x8002, 0x8000, 0x8001]);
+        assert_eq!(emu.trace_history()[1].instruction.opcode, 0x4C);
+
+        emu.enable_trace_history(0);
+        assert!(emu.trace_history().is_empty());
+    }
+
+    #[cfg(feature = "debugger")]
+    #[test]
+    fn mapper_irq_callback_ignores_i_flag() {
+        use alloc::sync::Arc;
+        use core::sync::atomic::{AtomicU32, Ordering};
+
+        // VRC7, its IRQ counter reaches $FF after 3 CPU cycles in cycle mode
+        let mut rom = nrom();
+        rom[6..8].copy_from_slice(&[0x50, 0x50]);
+        rom[16 + 0x2000..16 + 0x200C].copy_from_slice(&[
+            0x78, // SEI
+            0xA9, 0xFD, // LDA #$FD
+            0x8D, 0x10, 0xE0, // STA $E010, IRQ latch
+            0xA9, 0x06, // LDA #$06
+            0x8D, 0x00, 0xF0, // STA $F000, enable in cycle mode
+            0xEA, // NOP
+        ]);
+        rom[16 + 0x3FFC..16 + 0x3FFE].copy_from_slice(&[0x00, 0xE0]);
+
+        let mut emu = Emulator::new(&rom, None).unwrap();
+        let calls = Arc::new(AtomicU32::new(0));
+        let c = calls.clone();
+        emu.set_mapper_irq_callback(Box::new(move |_, _| {
+            c.fetch_add(1, Ordering::Relaxed);
+        }));
+
+        (0..6).for_each(|_| emu.step());
+        assert_eq!(emu.cpu().pc, 0xE00C);
+        // The IRQ stays asserted while the I flag masks it, it's only reported once
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+    }
+
+    #[cfg(feature = "debugger")]
+    #[test]
+    fn frame_events() {
+        let mut rom = nrom();
+        // LDA #$80, STA $2000, JMP $8000 (PPUCTRL writes are ignored during warmup)
+        rom[16..24].copy_from_slice(&[0xA9, 0x80, 0x8D, 0x00, 0x20, 0x4C, 0x00, 0x80]);
+        // NMI handler: LDA #$02, STA $4014, RTI
+        rom[16 + 0x10..16 + 0x16].copy_from_slice(&[0xA9, 0x02, 0x8D, 0x14, 0x40, 0x40]);
+        rom[16 + 0x3FFA..16 + 0x3FFE].copy_from_slice(&[0x10, 0x80, 0x00, 0x80]);
+
+        let mut emu = Emulator::new(&rom, None).unwrap();
+        emu.enable_frame_events(true);
+        for _ in 0..3 {
+            emu.clock_frame();
+        }
+
+        let events = emu.frame_events();
+        let kinds: alloc::vec::Vec<FrameEventKind> = events.iter().map(|e| e.kind).collect();
+        assert_eq!(kinds, [FrameEventKind::Nmi, FrameEventKind::OamDma]);
+        assert_eq!(events[0].scanline, 241);
+
+        emu.enable_frame_events(false);
+        assert!(emu.frame_events().is_empty());
+    }
+
+    #[test]
+    fn mirroring() {
+        let mut emu = Emulator::new(&nrom(), None).unwrap();
+        assert_eq!(emu.mirroring(), Mirroring::Horizontal);
+
+        emu.set_mirroring_override(Some(Mirroring::Vertical));
+        assert_eq!(emu.mirroring(), Mirroring::Vertical);
+        emu.set_mirroring_override(None);
+        assert_eq!(emu.mirroring(), Mirroring::Horizontal);
+    }
+
+    #[test]
+    fn lag_frame() {
+        let mut rom = nrom();
+        // JMP $8000
+        rom[16..19].copy_from_slice(&[0x4C, 0x00, 0x80]);
+        rom[16 + 0x3FFC..16 + 0x3FFE].copy_from_slice(&[0x00, 0x80]);
+        let mut emu = Emulator::new(&rom, None).unwrap();
+        emu.clock_frame();
+        assert!(emu.was_lag_frame());
+
+        // LDA $4017, JMP $8000
+        rom[16..22].copy_from_slice(&[0xAD, 0x17, 0x40, 0x4C, 0x00, 0x80]);
+        let mut emu = Emulator::new(&rom, None).unwrap();
+        emu.clock_frame();
+        assert!(!emu.was_lag_frame());
+    }
+
+    #[test]
+    fn run_for_duration() {
+        let mut emu = Emulator::new(&nrom(), None).unwrap();
+        let start = emu.elapsed_cycles();
+
+        emu.run_for_duration(1_000_000);
+        assert_eq!(emu.elapsed_cycles() - start, u64::from(NTSC_CPU_FREQUENCY));
+        assert!(emu.frame_number() >= 60);
+
+        // 1.789773 cycles per µs, the fractions add up
+        let start = emu.elapsed_cycles();
+        for _ in 0..1000 {
+            emu.run_for_duration(1);
+        }
+        assert_eq!(emu.elapsed_cycles() - start, 1789);
+    }
+
+    #[test]
+    fn overscan_crops_outputs() {
+        let mut emu = Emulator::new(&nrom(), None).unwrap();
+        emu.set_overscan(8, 8, 4, 4);
+        assert_eq!(emu.frame_size(), (248, 224));
+
+        let mut buffer = vec![0u8; RGBA_FRAME_SIZE];
+        assert_eq!(
+            emu.clock_frame_into(&mut buffer),
+            Err(BufferSizeError {
+                expected: 248 * 224 * 4,
+                actual: RGBA_FRAME_SIZE
+            })
+        );
+        let mut buffer = vec![0u8; 248 * 224 * 4];
+        assert_eq!(emu.clock_frame_into(&mut buffer), Ok(()));
+
+        let frame = emu.frame();
+        assert_eq!((frame.width(), frame.height()), (248, 224));
+        assert_eq!(frame.pixels().count(), 248 * 224);
+        assert_eq!(emu.clock_frame().pixels().count(), 248 * 224);
+        assert_eq!(emu.run_for_duration(20_000).pixels().count(), 248 * 224);
+        assert_eq!(emu.full_frame().len(), PpuFrame::WIDTH * PpuFrame::HEIGHT);
+    }
+
+    #[test]
+    fn press_controller1_releases_after_frames() {
+        let mut emu = Emulator::new(&nrom(), None).unwrap();
+        emu.set_controller1(ControllerState::RIGHT.bits());
+        emu.press_controller1(ControllerState::A, 2);
+        assert_eq!(emu.controller_ports[0].buttons(), 0x81);
+
+        emu.clock_frame();
+        assert_eq!(emu.controller_ports[0].buttons(), 0x81);
+        // Buttons that were not pressed by `press_controller1` stay held
+        emu.clock_frame();
+        assert_eq!(emu.controller_ports[0].buttons(), 0x01);
+    }
+
+    #[test]
+    fn press_controller1_again_restarts_hold() {
+        let mut emu = Emulator::new(&nrom(), None).unwrap();
+        emu.press_controller1(ControllerState::A, 3);
+        emu.clock_frame();
+
+        // The new duration replaces the 2 frames left
+        emu.press_controller1(ControllerState::A, 1);
+        emu.clock_frame();
+        assert_eq!(emu.controller_ports[0].buttons(), 0x00);
+
+        emu.press_controller1(ControllerState::A, 1);
+        emu.press_controller1(ControllerState::B, 3);
+        assert_eq!(emu.controller_ports[0].buttons(), 0x40);
+        emu.clock_frame();
+        emu.clock_frame();
+        assert_eq!(emu.controller_ports[0].buttons(), 0x40);
+        emu.clock_frame();
+        assert_eq!(emu.controller_ports[0].buttons(), 0x00);
+    }
+
+    #[test]
+    fn run_input_sequence() {
+        // The report only changes when the game strobes the controller
+        let mut rom = nrom();
+        rom[16..24].copy_from_slice(&[
+            0xA9, 0x01, // LDA #$01
+            0x8D, 0x16, 0x40, // STA $4016
+            0x4C, 0x00, 0x80, // JMP $8000
+        ]);
+        rom[16 + 0x3FFC..16 + 0x3FFE].copy_from_slice(&[0x00, 0x80]);
+
+        let mut emu = Emulator::new(&rom, None).unwrap();
+        let start = emu.frame_number();
+        emu.run_input_sequence(&[0x01, 0x80, 0x81]);
+        assert_eq!(emu.frame_number() - start, 3);
+        assert_eq!(emu.controller1_report(), 0x81);
+    }
+
+    #[test]
+    fn frame_stats() {
+        let mut emu = Emulator::new(&nrom(), None).unwrap();
+        emu.clock_frame();
+        // Not frame skipping
+        while !emu.clock_no_render() {}
+
+        emu.set_frame_skip(2);
+        emu.clock_frame();
+        emu.clock_frame();
+        assert_eq!(
+            emu.frame_stats(),
+            FrameStats {
+                rendered: 3,
+                skipped: 2,
+                skip_rate: 2,
+            }
+        );
+        assert_eq!(emu.frame_number(), 6);
+    }
+
+    #[cfg(feature = "debugger")]
+    #[test]
+    fn set_pc() {
+        let mut rom = nrom();
+        rom[16] = 0xEA; // NOP at $8000 and $C000
+
+        let mut emu = Emulator::new(&rom, None).unwrap();
+        emu.set_pc(0xC000);
+        assert_eq!(emu.current_instruction().0, 0xC000);
+        emu.step();
+        assert_eq!(emu.cpu().pc, 0xC001);
+    }
+
+    #[cfg(feature = "debugger")]
+    #[test]
+    fn stack() {
+        let mut rom = nrom();
+        rom[16..19].copy_from_slice(&[0x20, 0x00, 0x90]); // JSR $9000
+
+        let mut emu = Emulator::new(&rom, None).unwrap();
+        emu.set_pc(0x8000);
+        emu.step();
+
+        let (sp, page) = emu.stack();
+        assert_eq!(emu.cpu().pc, 0x9000);
+        // JSR pushes the address of its last byte, high byte first
+        assert_eq!(page[sp as usize + 1], 0x02);
+        assert_eq!(page[sp as usize + 2], 0x80);
+    }
+
+    #[cfg(feature = "debugger")]
+    #[test]
+    fn step_ppu() {
+        let mut rom = nrom();
+        rom[16] = 0xEA; // NOP
+        let mut emu = Emulator::new(&rom, None).unwrap();
+        emu.set_pc(0x8000);
+        emu.step();
+
+        let elapsed = emu.elapsed_cycles();
+        let (pc, cpu_cycles) = (emu.cpu().pc, emu.cpu().cycles);
+        let (mut scanline, mut cycle) = (emu.ppu.scanline(), emu.ppu.cycle());
+        let mut wrapped = false;
+        for _ in 0..400 {
+            let position = emu.step_ppu();
+            if cycle == 340 {
+                assert_eq!(position, (scanline + 1, 0));
+                wrapped = true;
+            } else {
+                assert_eq!(position, (scanline, cycle + 1));
+            }
+            scanline = position.0;
+            cycle = position.1;
+        }
+        assert!(wrapped);
+
+        assert_eq!(emu.elapsed_cycles(), elapsed);
+        assert_eq!((emu.cpu().pc, emu.cpu().cycles), (pc, cpu_cycles));
+    }
+
+    #[test]
+    fn clock_phase() {
+        let mut emu = Emulator::new(&nrom(), None).unwrap();
+        assert_eq!(emu.clock_phase(), 0);
+
+        let cycles = emu.elapsed_cycles();
+        emu.clock();
+        assert_eq!(emu.elapsed_cycles(), cycles + 1);
+        assert_eq!(emu.clock_phase(), 1);
+        emu.clock();
+        assert_eq!(emu.clock_phase(), 2);
+        emu.clock();
+        assert_eq!(emu.clock_phase(), 0);
+        assert_eq!(emu.elapsed_cycles(), cycles + 1);
+
+        emu.set_clock_divider(2);
+        emu.clock();
+        emu.clock();
+        assert_eq!(emu.clock_phase(), 0);
+        assert_eq!(emu.elapsed_cycles(), cycles + 2);
+    }
+
+    #[test]
+    #[cfg(feature = "debugger")]
+    fn watch_ram_write() {
+        use alloc::sync::Arc;
+        use core::sync::atomic::{AtomicU32, Ordering};
+
+        let mut rom = nrom();
+        rom[16..26].copy_from_slice(&[
+            0xA9, 0x2A, // LDA #$2A
+            0x8D, 0x10, 0x08, // STA $0810, mirror of $0010
+            0x8D, 0x11, 0x00, // STA $0011
+            0xE6, 0x10, // INC $10
+        ]);
+
+        let mut emu = Emulator::new(&rom, None).unwrap();
+        emu.set_pc(0x8000);
+        let writes = Arc::new(AtomicU32::new(0));
+        let last = Arc::new(AtomicU32::new(0));
+        let (w, l) = (writes.clone(), last.clone());
+        emu.watch_ram_write(
+            0x0010,
+            Box::new(move |addr, old, new| {
+                w.fetch_add(1, Ordering::Relaxed);
+                l.store(
+                    u32::from_be_bytes([0, addr as u8, old, new]),
+                    Ordering::Relaxed,
+                );
+            }),
+        );
+
+        (0..3).for_each(|_| emu.step());
+        assert_eq!(writes.load(Ordering::Relaxed), 1);
+        assert_eq!(last.load(Ordering::Relaxed), 0x00_10_00_2A);
+
+        emu.step(); // INC
+        assert_eq!(writes.load(Ordering::Relaxed), 2);
+        assert_eq!(last.load(Ordering::Relaxed), 0x00_10_2A_2B);
+
+        emu.clear_ram_watches();
+        emu.set_pc(0x8002);
+        emu.step();
+        assert_eq!(writes.load(Ordering::Relaxed), 2);
+    }
+
+    #[cfg(feature = "debugger")]
+    fn step_over_rom(subroutine: &[u8]) -> Emulator {
+        let mut rom = nrom();
+        rom[16..26].copy_from_slice(&[
+            0xA9, 0x80, // LDA #$80
+            0x8D, 0x00, 0x20, // STA $2000, enable NMI
+            0x20, 0x00, 0x90, // JSR $9000
+            0xEA, // NOP
+            0xEA, // NOP
+        ]);
+        rom[16 + 0x1000..16 + 0x1000 + subroutine.len()].copy_from_slice(subroutine);
+        // NMI handler: INC $10, RTI
+        rom[16 + 0x2000..16 + 0x2003].copy_from_slice(&[0xE6, 0x10, 0x40]);
+        rom[16 + 0x3FFA..16 + 0x3FFC].copy_from_slice(&[0x00, 0xA0]);
+
+        let mut emu = Emulator::new(&rom, None).unwrap();
+        // PPUCTRL is ignored during the warmup
+        emu.clock_frame();
+        emu.set_pc(0x8000);
+        emu.step();
+        emu.step();
+        emu
+    }
+
+    #[cfg(feature = "debugger")]
+    #[test]
+    fn step_over_returns() {
+        // LDA #$01, RTS
+        let mut emu = step_over_rom(&[0xA9, 0x01, 0x60]);
+        let st = emu.cpu().st;
+        assert!(emu.step_over());
+        assert_eq!(emu.cpu().pc, 0x8008);
+        assert_eq!(emu.cpu().st, st);
+        assert_eq!(emu.cpu().a, 0x01);
+
+        // Not a JSR, same as step
+        assert!(emu.step_over());
+        assert_eq!(emu.cpu().pc, 0x8009);
+    }
+
+    #[cfg(feature = "debugger")]
+    #[test]
+    fn step_over_runs_nmi() {
+        // Waits for the NMI handler: LDA $10, BEQ $9000, RTS
+        let mut emu = step_over_rom(&[0xA5, 0x10, 0xF0, 0xFC, 0x60]);
+        let st = emu.cpu().st;
+        assert!(emu.step_over());
+        assert_eq!(emu.cpu().pc, 0x8008);
+        assert_eq!(emu.cpu().st, st);
+        assert_eq!(emu.ram[0x10], 1);
+    }
+
+    #[cfg(feature = "debugger")]
+    #[test]
+    fn step_over_gives_up() {
+        // JMP $9000
+        let mut emu = step_over_rom(&[0x4C, 0x00, 0x90]);
+        let frame = emu.frame_number();
+        assert!(!emu.step_over());
+        assert_eq!(emu.frame_number() - frame, STEP_OVER_FRAME_LIMIT);
+    }
 }
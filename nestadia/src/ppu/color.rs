@@ -0,0 +1,152 @@
+use core::f32::consts::LN_2;
+
+use super::RGB_VALUE_TABLE;
+
+/// Adjustments applied to the palette when converting frames to RGB
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ColorCorrection {
+    /// Palette colors as they are in `RGB_VALUE_TABLE`
+    #[default]
+    None,
+    Adjusted(ColorAdjustment),
+}
+
+/// Picture controls, like the ones of a TV. The default value changes nothing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorAdjustment {
+    /// Added to each channel, from -1.0 (black) to 1.0 (white)
+    pub brightness: f32,
+    /// Scales the distance of each channel to mid-gray, 1.0 is unchanged
+    pub contrast: f32,
+    /// Scales the distance of each channel to the luminance of the color.
+    /// 0.0 gives grayscale, 1.0 is unchanged.
+    pub saturation: f32,
+    /// Channels are raised to the power of `1 / gamma`, values above 1.0 brighten
+    /// the midtones
+    pub gamma: f32,
+}
+
+impl Default for ColorAdjustment {
+    fn default() -> Self {
+        Self {
+            brightness: 0.0,
+            contrast: 1.0,
+            saturation: 1.0,
+            gamma: 1.0,
+        }
+    }
+}
+
+impl ColorCorrection {
+    /// Palette with the correction applied to every color
+    pub fn palette(&self) -> [[u8; 3]; 64] {
+        let adjustment = match self {
+            ColorCorrection::None => return RGB_VALUE_TABLE,
+            ColorCorrection::Adjusted(adjustment) => adjustment,
+        };
+
+        let mut palette = RGB_VALUE_TABLE;
+        for rgb in palette.iter_mut() {
+            *rgb = adjustment.apply(*rgb);
+        }
+        palette
+    }
+}
+
+impl ColorAdjustment {
+    fn apply(&self, rgb: [u8; 3]) -> [u8; 3] {
+        let channels = rgb.map(|c| f32::from(c) / 255.0);
+        // BT.601 weights, like `PpuFrame::to_luma`
+        let luma = 0.299 * channels[0] + 0.587 * channels[1] + 0.114 * channels[2];
+
+        channels.map(|c| {
+            let c = luma + (c - luma) * self.saturation;
+            let c = (c - 0.5) * self.contrast + 0.5;
+            let c = (c + self.brightness).clamp(0.0, 1.0);
+            let c = powf(c, 1.0 / self.gamma);
+            (c.clamp(0.0, 1.0) * 255.0 + 0.5) as u8
+        })
+    }
+}
+
+// The floating point functions of `std` are not available in `core`. These are
+// precise enough for 8-bit colors, on the range they are used.
+
+/// `x` raised to the power of `y`, for `x` in [0, 1]
+fn powf(x: f32, y: f32) -> f32 {
+    if x < f32::MIN_POSITIVE {
+        return 0.0;
+    }
+    exp(y * ln(x))
+}
+
+fn ln(x: f32) -> f32 {
+    // x = m * 2^e, with m in [1, 2)
+    let bits = x.to_bits();
+    let e = ((bits >> 23) & 0xFF) as i32 - 127;
+    let m = f32::from_bits((bits & 0x007F_FFFF) | 0x3F80_0000);
+
+    // ln(m) = 2 * atanh(z), the series converges quickly since z <= 1/3
+    let z = (m - 1.0) / (m + 1.0);
+    let z2 = z * z;
+    let atanh = z * (1.0 + z2 * (1.0 / 3.0 + z2 * (1.0 / 5.0 + z2 * (1.0 / 7.0 + z2 / 9.0))));
+
+    2.0 * atanh + e as f32 * LN_2
+}
+
+fn exp(x: f32) -> f32 {
+    // x = k * ln(2) + r, with r in (-ln(2), ln(2))
+    let k = (x / LN_2) as i32;
+    if k < -126 {
+        return 0.0;
+    }
+    if k > 127 {
+        return f32::INFINITY;
+    }
+    let r = x - k as f32 * LN_2;
+
+    // Taylor series, in Horner form
+    let mut exp_r = 1.0;
+    for n in (1..=9).rev() {
+        exp_r = 1.0 + r * exp_r / n as f32;
+    }
+
+    exp_r * f32::from_bits(((k + 127) as u32) << 23)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn neutral_adjustment_keeps_palette() {
+        assert_eq!(ColorCorrection::None.palette(), RGB_VALUE_TABLE);
+        assert_eq!(
+            ColorCorrection::Adjusted(ColorAdjustment::default()).palette(),
+            RGB_VALUE_TABLE
+        );
+    }
+
+    #[test]
+    fn adjustments() {
+        let gamma = ColorCorrection::Adjusted(ColorAdjustment {
+            gamma: 2.0,
+            ..ColorAdjustment::default()
+        });
+        // sqrt(0x7C / 255) * 255 = 177.8
+        assert_eq!(gamma.palette()[0x00], [0xB2, 0xB2, 0xB2]);
+
+        let grayscale = ColorCorrection::Adjusted(ColorAdjustment {
+            saturation: 0.0,
+            ..ColorAdjustment::default()
+        });
+        // 0.114 * 0xFC = 28.7
+        assert_eq!(grayscale.palette()[0x01], [0x1D, 0x1D, 0x1D]);
+
+        let brightness = ColorCorrection::Adjusted(ColorAdjustment {
+            brightness: 1.0,
+            ..ColorAdjustment::default()
+        });
+        assert_eq!(brightness.palette()[0x0D], [0xFF, 0xFF, 0xFF]);
+    }
+}
@@ -1,16 +1,305 @@
+#[cfg(feature = "debugger")]
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::ops::{Deref, DerefMut};
+
 use crate::bus::PpuBus;
 
 /// Registers definitions
 pub mod registers;
 
+mod color;
+mod rgb_value_table;
+
+pub use color::{ColorAdjustment, ColorCorrection};
+pub use rgb_value_table::RGB_VALUE_TABLE;
+
 pub const FRAME_WIDTH: usize = 256;
 pub const FRAME_HEIGHT: usize = 240;
 
-pub type PpuFrame = [u8; FRAME_WIDTH * FRAME_HEIGHT];
+/// Size in bytes of a frame converted to RGBA
+pub const RGBA_FRAME_SIZE: usize = FRAME_WIDTH * FRAME_HEIGHT * 4;
+
+/// Frame rendered by the PPU, as one palette index per pixel
+#[derive(Clone)]
+pub struct PpuFrame([u8; FRAME_WIDTH * FRAME_HEIGHT]);
+
+/// Frame output by the emulator, a view of a `PpuFrame` with the overscan area cropped out
+#[derive(Clone, Copy)]
+pub struct CroppedFrame<'a> {
+    frame: &'a PpuFrame,
+    overscan: Overscan,
+}
+
+/// Differences found between two frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameDiff {
+    /// Number of pixels that differ
+    pub count: usize,
+    /// Top-left corner (x, y) of the box bounding the differing pixels
+    pub top_left: (usize, usize),
+    /// Bottom-right corner (x, y) of the box bounding the differing pixels, inclusive
+    pub bottom_right: (usize, usize),
+}
+
+/// The buffer given to hold an RGBA frame doesn't have the right size
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferSizeError {
+    pub expected: usize,
+    pub actual: usize,
+}
+
+impl core::fmt::Display for BufferSizeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{:?}", &self)
+    }
+}
+
+/// Number of pixels hidden on each edge of the frame, like a TV would
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Overscan {
+    pub top: u8,
+    pub bottom: u8,
+    pub left: u8,
+    pub right: u8,
+}
+
+impl Overscan {
+    /// Width of the visible area
+    pub fn width(&self) -> usize {
+        FRAME_WIDTH.saturating_sub(usize::from(self.left) + usize::from(self.right))
+    }
+
+    /// Height of the visible area
+    pub fn height(&self) -> usize {
+        FRAME_HEIGHT.saturating_sub(usize::from(self.top) + usize::from(self.bottom))
+    }
+}
+
+impl<'a> CroppedFrame<'a> {
+    pub fn new(frame: &'a PpuFrame, overscan: Overscan) -> Self {
+        Self { frame, overscan }
+    }
+
+    /// Width of the output in pixels, once the overscan is cropped
+    pub fn width(&self) -> usize {
+        self.overscan.width()
+    }
+
+    /// Height of the output in pixels, once the overscan is cropped
+    pub fn height(&self) -> usize {
+        self.overscan.height()
+    }
+
+    pub fn overscan(&self) -> Overscan {
+        self.overscan
+    }
+
+    /// Palette indexes of the visible pixels, row by row
+    pub fn pixels(&self) -> impl Iterator<Item = u8> + 'a {
+        self.frame.cropped(self.overscan)
+    }
+
+    /// Whole frame, including the overscan area
+    pub fn full(&self) -> &'a PpuFrame {
+        self.frame
+    }
+
+    /// Converts the visible pixels to RGBA into `buffer`, which must be
+    /// `width() * height() * 4` bytes long
+    pub fn to_rgba(&self, buffer: &mut [u8]) -> Result<(), BufferSizeError> {
+        self.to_rgba_with_palette(&RGB_VALUE_TABLE, buffer)
+    }
+
+    /// Same as `to_rgba`, with the RGB values of the 64 colors given by `palette`
+    pub fn to_rgba_with_palette(
+        &self,
+        palette: &[[u8; 3]; 64],
+        buffer: &mut [u8],
+    ) -> Result<(), BufferSizeError> {
+        self.frame.to_rgba_cropped(self.overscan, palette, buffer)
+    }
+}
+
+impl Default for PpuFrame {
+    fn default() -> Self {
+        Self([0u8; FRAME_WIDTH * FRAME_HEIGHT])
+    }
+}
+
+impl Deref for PpuFrame {
+    type Target = [u8; FRAME_WIDTH * FRAME_HEIGHT];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for PpuFrame {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl PpuFrame {
+    /// Width of the frame buffer in pixels
+    pub const WIDTH: usize = FRAME_WIDTH;
+    /// Height of the frame buffer in pixels, the whole picture is rendered regardless of the
+    /// region. The size of the output left by the overscan is given by `CroppedFrame::width`
+    /// and `CroppedFrame::height`.
+    pub const HEIGHT: usize = FRAME_HEIGHT;
+
+    /// Pixels left visible by the overscan, row by row
+    pub fn cropped(&self, overscan: Overscan) -> impl Iterator<Item = u8> + '_ {
+        let left = usize::from(overscan.left);
+        let width = overscan.width();
+
+        self.0
+            .chunks_exact(FRAME_WIDTH)
+            .skip(usize::from(overscan.top))
+            .take(overscan.height())
+            .flat_map(move |row| row[left..left + width].iter().copied())
+    }
+
+    /// Converts the frame to RGBA into `buffer`, which must be `RGBA_FRAME_SIZE` bytes long
+    pub fn to_rgba(&self, buffer: &mut [u8]) -> Result<(), BufferSizeError> {
+        self.to_rgba_with_palette(&RGB_VALUE_TABLE, buffer)
+    }
+
+    /// Same as `to_rgba`, with the RGB values of the 64 colors given by `palette`,
+    /// see `ColorCorrection::palette`
+    pub fn to_rgba_with_palette(
+        &self,
+        palette: &[[u8; 3]; 64],
+        buffer: &mut [u8],
+    ) -> Result<(), BufferSizeError> {
+        self.to_rgba_cropped(Overscan::default(), palette, buffer)
+    }
+
+    /// Converts the pixels left visible by the overscan to RGBA into `buffer`, which must
+    /// be `overscan.width() * overscan.height() * 4` bytes long
+    pub fn to_rgba_cropped(
+        &self,
+        overscan: Overscan,
+        palette: &[[u8; 3]; 64],
+        buffer: &mut [u8],
+    ) -> Result<(), BufferSizeError> {
+        let expected = overscan.width() * overscan.height() * 4;
+        if buffer.len() != expected {
+            return Err(BufferSizeError {
+                expected,
+                actual: buffer.len(),
+            });
+        }
+
+        for (pixel, color) in buffer.chunks_exact_mut(4).zip(self.cropped(overscan)) {
+            let rgb = palette[usize::from(color & 0x3F)];
+            pixel[..3].copy_from_slice(&rgb);
+
+            // Alpha is always 0xff because it's opaque
+            pixel[3] = 0xFF;
+        }
+
+        Ok(())
+    }
+
+    /// Converts the frame to one 8-bit luminance value per pixel.
+    /// Uses the BT.601 weights (0.299 R + 0.587 G + 0.114 B) on the palette colors.
+    pub fn to_luma(&self) -> Vec<u8> {
+        let mut luma_table = [0u8; 64];
+        for (luma, rgb) in luma_table.iter_mut().zip(RGB_VALUE_TABLE.iter()) {
+            // Fixed point weights, in 256ths
+            let sum = 77 * u32::from(rgb[0]) + 150 * u32::from(rgb[1]) + 29 * u32::from(rgb[2]);
+            *luma = (sum >> 8) as u8;
+        }
+
+        self.0
+            .iter()
+            .map(|color| luma_table[usize::from(color & 0x3F)])
+            .collect()
+    }
+
+    /// Compares the palette indexes of two frames.
+    /// Returns `None` if they are identical.
+    pub fn diff(&self, other: &PpuFrame) -> Option<FrameDiff> {
+        let mut diff: Option<FrameDiff> = None;
+
+        for (i, (a, b)) in self.0.iter().zip(other.0.iter()).enumerate() {
+            if a == b {
+                continue;
+            }
+
+            let x = i % FRAME_WIDTH;
+            let y = i / FRAME_WIDTH;
+
+            match &mut diff {
+                Some(diff) => {
+                    diff.count += 1;
+                    diff.top_left.0 = diff.top_left.0.min(x);
+                    diff.top_left.1 = diff.top_left.1.min(y);
+                    diff.bottom_right.0 = diff.bottom_right.0.max(x);
+                    diff.bottom_right.1 = diff.bottom_right.1.max(y);
+                }
+                None => {
+                    diff = Some(FrameDiff {
+                        count: 1,
+                        top_left: (x, y),
+                        bottom_right: (x, y),
+                    })
+                }
+            }
+        }
+
+        diff
+    }
+}
+
+/// Trade-off between emulation accuracy and speed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Accuracy {
+    /// Background pixels are rendered one per PPU cycle
+    CycleAccurate,
+    /// Background is rendered a scanline at a time, at the end of its visible part.
+    /// Mid-scanline register changes (raster effects) are not visible.
+    Fast,
+}
 
 // TODO: at some point, we need to set the StatusReg::SPRITE_OVERFLOW flag!
 // See: https://wiki.nesdev.com/w/index.php/PPU_sprite_evaluation#Sprite_overflow_bug
 
+/// Decoded PPU status register (PPUSTATUS)
+#[cfg(feature = "debugger")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PpuStatusFlags {
+    pub vblank: bool,
+    pub sprite_zero_hit: bool,
+    /// Never set for now, sprite overflow isn't emulated
+    pub sprite_overflow: bool,
+}
+
+/// Sprite as stored in OAM, with its attributes decoded
+#[cfg(feature = "debugger")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sprite {
+    /// Top of the sprite, minus one: sprites are displayed one line below
+    pub y: u8,
+    /// Tile index. For 8x16 sprites, bit 0 selects the pattern table instead of PPUCTRL.
+    pub tile: u8,
+    /// Raw attributes byte
+    pub attributes: u8,
+    pub x: u8,
+    /// Sprite palette, from 4 to 7
+    pub palette: u8,
+    pub behind_background: bool,
+    pub flip_horizontal: bool,
+    pub flip_vertical: bool,
+}
+
+/// Called on PPU register writes with the register address ($2000-$2007), the value
+/// written and the current scanline and cycle
+#[cfg(feature = "debugger")]
+pub type PpuWriteCallback = Box<dyn FnMut(u16, u8, i16, u16) + Send>;
+
 pub struct Ppu {
     // Internal memory
     palette_table: [u8; 32], // For color stuff
@@ -21,15 +310,28 @@ pub struct Ppu {
     mask_reg: registers::MaskReg,
     status_reg: registers::StatusReg,
     oam_addr_reg: u8,
-    scroll_reg: registers::ScrollReg,
-    addr_reg: registers::VramAddr, // Address register pointing to name tables
+    addr_reg: registers::VramAddr, // Address register and scroll (loopy registers)
 
     // Emulation-specific internal stuff
     cycle_count: u16,
     scanline: i16,
     frame: PpuFrame,
+    bg_opaque: [bool; FRAME_WIDTH * FRAME_HEIGHT], // Background pixels that are not color 0
     vblank_nmi_set: bool,
+    vblank_suppressed: bool,
     last_data_on_bus: u8,
+    io_latch: u8, // Last value written or read on the CPU-PPU data bus (decay not emulated)
+    warming_up: bool, // From reset to the first pre-render line, some writes are ignored
+    accuracy: Accuracy,
+    color_correction: ColorCorrection,
+    rgb_palette: [[u8; 3]; 64], // RGB_VALUE_TABLE with the color correction applied
+
+    #[cfg(feature = "debugger")]
+    sprite_counts: [u8; FRAME_HEIGHT],
+    #[cfg(feature = "debugger")]
+    write_callback: Option<PpuWriteCallback>,
+    #[cfg(feature = "debugger")]
+    force_show_left_edge: bool,
 }
 
 impl Default for Ppu {
@@ -48,14 +350,27 @@ impl Ppu {
             mask_reg: registers::MaskReg::default(),
             status_reg: registers::StatusReg::default(),
             oam_addr_reg: 0,
-            scroll_reg: registers::ScrollReg::default(),
             addr_reg: registers::VramAddr::default(),
 
             cycle_count: 0,
             scanline: 0,
-            frame: [0u8; 256 * 240],
+            frame: PpuFrame::default(),
+            bg_opaque: [false; FRAME_WIDTH * FRAME_HEIGHT],
             vblank_nmi_set: false,
+            vblank_suppressed: false,
             last_data_on_bus: 0,
+            io_latch: 0,
+            warming_up: false,
+            accuracy: Accuracy::CycleAccurate,
+            color_correction: ColorCorrection::None,
+            rgb_palette: RGB_VALUE_TABLE,
+
+            #[cfg(feature = "debugger")]
+            sprite_counts: [0u8; FRAME_HEIGHT],
+            #[cfg(feature = "debugger")]
+            write_callback: None,
+            #[cfg(feature = "debugger")]
+            force_show_left_edge: false,
         }
     }
 
@@ -66,12 +381,99 @@ impl Ppu {
         self.mask_reg = registers::MaskReg::default();
         self.status_reg = registers::StatusReg::default();
         self.oam_addr_reg = 0;
-        self.scroll_reg = registers::ScrollReg::default();
         self.addr_reg = registers::VramAddr::default();
         self.cycle_count = 0;
         self.scanline = 0;
-        self.frame = [0u8; FRAME_WIDTH * FRAME_HEIGHT];
+        self.frame = PpuFrame::default();
+        self.bg_opaque = [false; FRAME_WIDTH * FRAME_HEIGHT];
         self.vblank_nmi_set = false;
+        self.vblank_suppressed = false;
+        self.warming_up = true;
+    }
+
+    /// Scanline being rendered, -1 being the pre-render line
+    #[cfg(feature = "debugger")]
+    pub fn scanline(&self) -> i16 {
+        self.scanline
+    }
+
+    /// Cycle within the current scanline (0-340)
+    #[cfg(feature = "debugger")]
+    pub fn cycle(&self) -> u16 {
+        self.cycle_count
+    }
+
+    /// Shows the leftmost 8 pixels of background and sprites even when PPUMASK hides them
+    #[cfg(feature = "debugger")]
+    pub fn set_force_show_left_edge(&mut self, force: bool) {
+        self.force_show_left_edge = force;
+    }
+
+    #[cfg(feature = "debugger")]
+    pub fn set_write_callback(&mut self, callback: Option<PpuWriteCallback>) {
+        self.write_callback = callback;
+    }
+
+    /// Number of sprites found on a visible scanline of the current frame.
+    /// Unlike the hardware, evaluation doesn't stop at 8 so overflows can be spotted.
+    #[cfg(feature = "debugger")]
+    pub fn sprites_on_scanline(&self, line: i16) -> u8 {
+        use core::convert::TryFrom;
+
+        usize::try_from(line)
+            .ok()
+            .and_then(|line| self.sprite_counts.get(line))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Sprite `index` of OAM, from 0 to 63. Higher indexes wrap around.
+    #[cfg(feature = "debugger")]
+    pub fn sprite(&self, index: u8) -> Sprite {
+        let offset = usize::from(index & 0x3F) * 4;
+        let [y, tile, attributes, x] = [
+            self.oam_data[offset],
+            self.oam_data[offset + 1],
+            self.oam_data[offset + 2],
+            self.oam_data[offset + 3],
+        ];
+
+        Sprite {
+            y,
+            tile,
+            attributes,
+            x,
+            palette: (attributes & 0x03) + 4,
+            behind_background: attributes & 0x20 == 0x20,
+            flip_horizontal: attributes & 0x40 == 0x40,
+            flip_vertical: attributes & 0x80 == 0x80,
+        }
+    }
+
+    #[cfg(feature = "debugger")]
+    fn evaluate_sprites(&mut self) {
+        // Same lines as `render_sprites`, from the OAM Y coordinate
+        let line = self.scanline;
+        let height = i16::from(self.ctrl_reg.sprite_size());
+        let count = self
+            .oam_data
+            .chunks_exact(4)
+            .map(|sprite| line - i16::from(sprite[0]))
+            .filter(|row| (0..height).contains(row))
+            .count();
+        self.sprite_counts[self.scanline as usize] = count as u8;
+    }
+
+    #[cfg(feature = "debugger")]
+    pub fn notify_register_write(&mut self, addr: u16, data: u8) {
+        if let Some(callback) = &mut self.write_callback {
+            callback(
+                0x2000 | (addr & 0x0007),
+                data,
+                self.scanline,
+                self.cycle_count,
+            );
+        }
     }
 
     pub fn take_vblank_nmi_set_state(&mut self) -> bool {
@@ -82,6 +484,14 @@ impl Ppu {
 
     pub fn write(&mut self, bus: &mut PpuBus<'_>, addr: u16, data: u8) {
         let addr = addr & 0x07; // mirror
+        self.io_latch = data;
+
+        // PPUCTRL, PPUMASK, PPUSCROLL and PPUADDR ignore writes until the end of the first
+        // vblank after reset, about 29658 CPU cycles
+        if self.warming_up && matches!(addr, 0 | 1 | 5 | 6) {
+            log::debug!("Ignored write on PPU register {:#X} during warmup", addr);
+            return;
+        }
 
         match addr {
             0 => {
@@ -91,6 +501,7 @@ impl Ppu {
                     self.ctrl_reg.contains(registers::ControlReg::GENERATE_NMI);
 
                 self.ctrl_reg.write(data);
+                self.addr_reg.write_nametable(data);
 
                 let postwrite_generate_nmi_ctrl_state =
                     self.ctrl_reg.contains(registers::ControlReg::GENERATE_NMI);
@@ -103,6 +514,13 @@ impl Ppu {
                     && is_in_vblank
                 {
                     self.vblank_nmi_set = true;
+                } else if prewrite_generate_nmi_ctrl_state
+                    && !postwrite_generate_nmi_ctrl_state
+                    && self.scanline == 241
+                    && self.cycle_count <= 1
+                {
+                    // Disabling NMI right as VBL is set cancels the NMI before the CPU sees it
+                    self.vblank_nmi_set = false;
                 }
             }
             1 => {
@@ -124,8 +542,8 @@ impl Ppu {
                 self.oam_addr_reg = self.oam_addr_reg.wrapping_add(1);
             }
             5 => {
-                // Write Scroll register
-                self.scroll_reg.write(data);
+                // Write Scroll register, sharing the write toggle with PPUADDR
+                self.addr_reg.write_scroll(data);
             }
             6 => {
                 // Write PPU Address
@@ -148,14 +566,8 @@ impl Ppu {
                     // Unused addresses
                     0x3000..=0x3EFF => log::warn!("address space 0x3000..0x3EFF is not expected to be used, but it was attempted to write at 0x{:#X}", write_addr),
 
-                    // Palette table:
-                    // Mirror some specific addresses to $3F00/$3F04/$3F08/$3F0C
-                    // (usually, used for transparency)
-                    0x3F10 | 0x3F14 | 0x3F18 | 0x3F1C => {
-                        let write_addr_mirror = write_addr - 0x0010;
-                        self.palette_table[(write_addr_mirror % 32) as usize] = data;
-                    }
-                    0x3F00..=0x3FFF => self.palette_table[(write_addr % 32) as usize] = data,
+                    // Palette table
+                    0x3F00..=0x3FFF => self.palette_table[palette_index(write_addr)] = data,
 
                     _ => unreachable!("unexpected write to mirrored space {:#X}", write_addr),
                 }
@@ -177,29 +589,41 @@ impl Ppu {
     pub fn read(&mut self, bus: &mut PpuBus<'_>, addr: u16) -> u8 {
         let addr = addr & 0x07; // mirror
 
-        match addr {
+        let data = match addr {
             // Not readable addresses
             0 | 1 | 3 | 5 | 6 => {
                 // Control, mask, OAM address, scroll, PPU Address
-                log::warn!(
+                // Open bus: the last value seen on the CPU-PPU data bus is returned
+                log::debug!(
                     "Attempted to read write-only PPU address: {:#X} (culprit at {})",
                     addr,
                     core::panic::Location::caller()
                 );
-                0
+                self.io_latch
             }
 
             // Readable addresses
             2 => {
                 // Read Status
 
-                // 3 top bits are the PPU status, least significant bits are noise from PPU bus.
-                let snapshot = self.status_reg.read() | self.last_data_on_bus & 0x1F;
+                // 3 top bits are the PPU status, least significant bits are open bus.
+                let snapshot = self.status_reg.read() | (self.io_latch & 0x1F);
+
+                // VBL flag race condition
+                // See: http://wiki.nesdev.com/w/index.php/PPU_frame_timing#VBL_Flag_Timing
+                if self.scanline == 240 && self.cycle_count == 340 {
+                    // Reading one PPU clock before the flag is set reads it as clear and
+                    // prevents both the flag and the NMI for this frame.
+                    self.vblank_suppressed = true;
+                } else if self.scanline == 241 && self.cycle_count <= 1 {
+                    // Reading on the same clock or one later reads it as set, but the NMI
+                    // is still suppressed for this frame.
+                    self.vblank_nmi_set = false;
+                }
 
                 // Reading the Status register clear bit 7 and also the address latch used by PPUSCROLL and PPUADDR.
                 self.status_reg.remove(registers::StatusReg::VBLANK_STARTED);
                 self.addr_reg.reset_latch();
-                self.scroll_reg.reset_latch();
 
                 snapshot
             }
@@ -237,13 +661,7 @@ impl Ppu {
                     }
 
                     // Palette table is not behind bus, it can be directly returned.
-                    // Mirror some specific addresses to $3F00/$3F04/$3F08/$3F0C
-                    // (usually, used for transparency)
-                    0x3F10 | 0x3F14 | 0x3F18 | 0x3F1C => {
-                        let read_addr_mirror = read_addr - 0x10;
-                        self.palette_table[(read_addr_mirror - 0x3F00) as usize]
-                    }
-                    0x3F00..=0x3FFF => self.palette_table[usize::from(read_addr - 0x3F00)],
+                    0x3F00..=0x3FFF => self.palette_table[palette_index(read_addr)],
 
                     _ => unreachable!("unexpected access to mirrored space {:#X}", read_addr),
                 }
@@ -252,9 +670,101 @@ impl Ppu {
             _ => {
                 unreachable!("unexpected access to mirrored space {:#X}", addr);
             }
+        };
+
+        self.io_latch = data;
+        data
+    }
+
+    pub fn frame(&self) -> &PpuFrame {
+        &self.frame
+    }
+
+    /// Reads PPU memory without touching the address latch or the read buffer
+    #[cfg(feature = "debugger")]
+    pub fn vram_read(&self, bus: &mut PpuBus<'_>, addr: u16) -> u8 {
+        let addr = addr & 0x3FFF;
+        match addr {
+            0..=0x1FFF => bus.read_chr_mem(addr),
+            0x2000..=0x3EFF => bus.read_name_tables(addr),
+            _ => self.palette_table[palette_index(addr)],
+        }
+    }
+
+    /// Writes PPU memory without touching the address latch
+    #[cfg(feature = "debugger")]
+    pub fn vram_write(&mut self, bus: &mut PpuBus<'_>, addr: u16, data: u8) {
+        let addr = addr & 0x3FFF;
+        match addr {
+            0..=0x1FFF => bus.write_chr_mem(addr, data),
+            0x2000..=0x3EFF => bus.write_name_tables(addr, data),
+            _ => self.palette_table[palette_index(addr)] = data,
         }
     }
 
+    pub fn set_accuracy(&mut self, accuracy: Accuracy) {
+        self.accuracy = accuracy;
+    }
+
+    pub fn accuracy(&self) -> Accuracy {
+        self.accuracy
+    }
+
+    /// Last value written on PPUCTRL ($2000)
+    #[cfg(feature = "debugger")]
+    pub fn ctrl(&self) -> u8 {
+        self.ctrl_reg.bits()
+    }
+
+    /// Last value written on PPUMASK ($2001)
+    #[cfg(feature = "debugger")]
+    pub fn mask(&self) -> u8 {
+        self.mask_reg.bits()
+    }
+
+    /// Status flags, read without the side effects of a $2002 read
+    #[cfg(feature = "debugger")]
+    pub fn status_flags(&self) -> PpuStatusFlags {
+        PpuStatusFlags {
+            vblank: self
+                .status_reg
+                .contains(registers::StatusReg::VBLANK_STARTED),
+            sprite_zero_hit: self
+                .status_reg
+                .contains(registers::StatusReg::SPRITE_ZERO_HIT),
+            sprite_overflow: self
+                .status_reg
+                .contains(registers::StatusReg::SPRITE_OVERFLOW),
+        }
+    }
+
+    /// Sets the correction applied to the palette by `rgb_palette`.
+    /// Frames keep palette indexes, only the RGB conversion is affected.
+    pub fn set_color_correction(&mut self, correction: ColorCorrection) {
+        self.color_correction = correction;
+        self.rgb_palette = correction.palette();
+    }
+
+    pub fn color_correction(&self) -> ColorCorrection {
+        self.color_correction
+    }
+
+    /// RGB values of the 64 colors, with the color correction applied
+    pub fn rgb_palette(&self) -> &[[u8; 3]; 64] {
+        &self.rgb_palette
+    }
+
+    /// Universal background color ($3F00), as an index in `RGB_VALUE_TABLE`
+    pub fn backdrop_color(&self) -> u8 {
+        self.palette_table[0] & 0x3F
+    }
+
+    /// Whether PPUMASK enables background or sprite rendering
+    pub fn rendering_enabled(&self) -> bool {
+        self.mask_reg
+            .intersects(registers::MaskReg::SHOW_BACKGROUND | registers::MaskReg::SHOW_SPRITES)
+    }
+
     pub fn ready_frame(&mut self) -> Option<&PpuFrame> {
         if self.cycle_count == 0 && self.scanline == -1 {
             // Yeah! We got a frame ready
@@ -266,6 +776,15 @@ impl Ppu {
 
     /// Returns frame when it's ready
     pub fn clock(&mut self, bus: &mut PpuBus) {
+        self.step(bus, true);
+    }
+
+    /// Advances timing, status flags and interrupts like `clock`, without rendering
+    pub fn clock_no_render(&mut self, bus: &mut PpuBus) {
+        self.step(bus, false);
+    }
+
+    fn step(&mut self, bus: &mut PpuBus, render: bool) {
         self.cycle_count += 1;
 
         if self.cycle_count >= 341 {
@@ -276,15 +795,23 @@ impl Ppu {
 
             self.cycle_count = 0;
             self.scanline += 1;
-            bus.irq_scanline();
+            bus.irq_scanline(self.scanline);
+
+            #[cfg(feature = "debugger")]
+            if (0..FRAME_HEIGHT as i16).contains(&self.scanline) {
+                self.evaluate_sprites();
+            }
 
             if self.scanline == 241 {
-                self.status_reg.insert(registers::StatusReg::VBLANK_STARTED);
                 self.status_reg
                     .remove(registers::StatusReg::SPRITE_ZERO_HIT);
-                if self.ctrl_reg.contains(registers::ControlReg::GENERATE_NMI) {
-                    self.vblank_nmi_set = true;
+                if !self.vblank_suppressed {
+                    self.status_reg.insert(registers::StatusReg::VBLANK_STARTED);
+                    if self.ctrl_reg.contains(registers::ControlReg::GENERATE_NMI) {
+                        self.vblank_nmi_set = true;
+                    }
                 }
+                self.vblank_suppressed = false;
             } else if self.scanline >= 261 {
                 // http://wiki.nesdev.com/w/index.php/PPU_rendering#Pre-render_scanline_.28-1_or_261.29
                 // scanline = -1 is the dummy scanline
@@ -292,13 +819,24 @@ impl Ppu {
 
                 // VBLANK is done
                 self.status_reg.remove(registers::StatusReg::VBLANK_STARTED);
-
-                // FIXME: temporary workaround for quick and dirty, but somewhat working, rendering
-                self.dump_sprites(bus);
+                self.warming_up = false;
             }
         }
 
-        self.render_pixel(bus);
+        if !render {
+            return;
+        }
+
+        match self.accuracy {
+            Accuracy::CycleAccurate => self.render_pixel(bus),
+            Accuracy::Fast if self.cycle_count == 256 => self.render_scanline(bus),
+            Accuracy::Fast => (),
+        }
+
+        // Sprites are drawn over the background of the line once it is done
+        if self.cycle_count == 256 {
+            self.render_sprites(bus);
+        }
     }
 
     fn render_pixel(&mut self, bus: &mut PpuBus) {
@@ -308,15 +846,46 @@ impl Ppu {
             return;
         }
 
-        let scroll_x = self.scroll_reg.scroll_x();
-        let scroll_y = self.scroll_reg.scroll_y();
+        let x = self.cycle_count;
+        let y = u16::try_from(self.scanline).unwrap();
 
-        let bank = self.ctrl_reg.background_pattern_base_addr();
-        let nametable_base_addr = self.ctrl_reg.nametable_base_addr();
+        let (tile_row, palette, fine_x) = self.bg_tile_row(bus, x, y);
+        self.set_bg_pixel(x, y, tile_pattern(tile_row, fine_x), palette);
+    }
+
+    /// Renders the background of the whole current scanline, fetching each tile once
+    fn render_scanline(&mut self, bus: &mut PpuBus) {
+        use core::convert::TryFrom;
+
+        if self.scanline < 0 || self.scanline > 239 {
+            return;
+        }
 
-        let x = self.cycle_count;
         let y = u16::try_from(self.scanline).unwrap();
 
+        let mut x = 0;
+        while x < FRAME_WIDTH as u16 {
+            let (tile_row, palette, fine_x) = self.bg_tile_row(bus, x, y);
+            for fine_x in fine_x..8 {
+                if x >= FRAME_WIDTH as u16 {
+                    break;
+                }
+
+                self.set_bg_pixel(x, y, tile_pattern(tile_row, fine_x), palette);
+                x += 1;
+            }
+        }
+    }
+
+    /// Fetches the background tile row under screen pixel (x, y).
+    /// Returns the pattern bytes (low, high), the palette and the pixel column within the tile.
+    fn bg_tile_row(&mut self, bus: &mut PpuBus, x: u16, y: u16) -> ((u8, u8), [u8; 4], u16) {
+        let scroll_x = self.addr_reg.scroll_x();
+        let scroll_y = self.addr_reg.scroll_y();
+
+        let bank = self.ctrl_reg.background_pattern_base_addr();
+        let nametable_base_addr = self.addr_reg.nametable_base_addr();
+
         let x_scrolled = x.wrapping_add(scroll_x as u16);
         let y_scrolled = y.wrapping_add(scroll_y as u16);
 
@@ -342,22 +911,18 @@ impl Ppu {
 
         let tile = bus.read_name_tables(nametable_base_addr + tile_idx);
 
-        let pat_x = 7 - x_scrolled % 8;
         let pat_y = y_scrolled % 8;
-        let lo = (bus.read_chr_mem(bank + u16::from(tile) * 16 + pat_y) >> pat_x) & 0b1;
-        let hi = (bus.read_chr_mem(bank + u16::from(tile) * 16 + pat_y + 8) >> pat_x) & 0b1;
-
-        let pat = hi << 1 | lo;
+        let lo = bus.read_chr_mem(bank + u16::from(tile) * 16 + pat_y);
+        let hi = bus.read_chr_mem(bank + u16::from(tile) * 16 + pat_y + 8);
 
         let palette = self.bg_palette(bus, tile_x, tile_y, quadrant);
-        let color = palette[pat as usize];
 
-        self.set_pixel(x, y, color);
+        ((lo, hi), palette, x_scrolled % 8)
     }
 
     fn bg_palette(&mut self, bus: &mut PpuBus, tile_x: u16, tile_y: u16, quadrant: u8) -> [u8; 4] {
         let attr_table_idx = (tile_y / 4) * 8 + (tile_x / 4);
-        let nametable_base_addr = self.ctrl_reg.nametable_base_addr();
+        let nametable_base_addr = self.addr_reg.nametable_base_addr();
 
         let offset = match quadrant & 0b11 {
             0b00 => 0x3c0,
@@ -389,129 +954,79 @@ impl Ppu {
         ]
     }
 
-    // this is mostly for quick debugging
-    fn dump_sprites(&mut self, bus: &mut PpuBus) {
-        for i in (0..self.oam_data.len()).step_by(4) {
-            let behind_background = self.oam_data[i + 2] >> 5 & 1 == 1;
-            if behind_background {
-                continue;
-            }
+    /// Draws the sprites covering the current scanline. Their patterns are fetched at this
+    /// point, so CHR banks switched mid-frame apply to the following lines.
+    fn render_sprites(&mut self, bus: &mut PpuBus) {
+        use core::convert::TryFrom;
 
-            let tile_idx = u16::from(self.oam_data[i + 1]);
-            let tile_x = u16::from(self.oam_data[i + 3]);
-            let tile_y = u16::from(self.oam_data[i]);
+        if self.scanline < 0 || self.scanline > 239 {
+            return;
+        }
 
-            let flip_vertical = self.oam_data[i + 2] >> 7 & 1 == 1;
+        let y = u16::try_from(self.scanline).unwrap();
+        let height = u16::from(self.ctrl_reg.sprite_size());
 
-            let flip_horizontal = self.oam_data[i + 2] >> 6 & 1 == 1;
+        // Pixels already covered by an opaque pixel of a sprite with a lower index,
+        // which has priority even when it is itself hidden behind the background
+        let mut covered = [false; FRAME_WIDTH];
 
-            // find sprite palette
-            let palette_idx = self.oam_data[i + 2] & 0b11;
-            let sprite_palette = self.sprite_palette(palette_idx);
+        for i in (0..self.oam_data.len()).step_by(4) {
+            let tile_y = u16::from(self.oam_data[i]);
+            let tile_idx = u16::from(self.oam_data[i + 1]);
+            let attributes = self.oam_data[i + 2];
+            let tile_x = u16::from(self.oam_data[i + 3]);
+
+            if y < tile_y || y >= tile_y + height {
+                continue;
+            }
 
-            // load tile pattern
-            let bank: u16 = self.ctrl_reg.sprite_pattern_base_addr();
-            let mut tile = [0u8; 16];
+            let flip_vertical = attributes >> 7 & 1 == 1;
+            let flip_horizontal = attributes >> 6 & 1 == 1;
+            let behind_background = attributes >> 5 & 1 == 1;
 
-            if self.ctrl_reg.sprite_size() == 8 {
-                // 8x8 sprites
-                for i in 0..16 {
-                    tile[i as usize] = bus.read_chr_mem(bank + tile_idx * 16 + i);
-                }
-                self.dump_tile(
-                    sprite_palette,
-                    flip_horizontal,
-                    flip_vertical,
-                    tile,
-                    tile_x,
-                    tile_y,
-                );
+            // Vertical flip applies to the whole sprite, swapping the tiles of 8x16 sprites
+            let row = if flip_vertical {
+                height - 1 - (y - tile_y)
             } else {
-                // 8x16 sprites
-                if !flip_vertical {
-                    for i in 0..16 {
-                        tile[i as usize] = bus.read_chr_mem(bank + (tile_idx & 0xFE) * 16 + i);
-                    }
-                    self.dump_tile(
-                        sprite_palette,
-                        flip_horizontal,
-                        flip_vertical,
-                        tile,
-                        tile_x,
-                        tile_y,
-                    );
-
-                    for i in 0..16 {
-                        tile[i as usize] =
-                            bus.read_chr_mem(bank + ((tile_idx & 0xFE) + 1) * 16 + i);
-                    }
-                    self.dump_tile(
-                        sprite_palette,
-                        flip_horizontal,
-                        flip_vertical,
-                        tile,
-                        tile_x,
-                        tile_y + 8,
-                    );
-                } else {
-                    for i in 0..16 {
-                        tile[i as usize] =
-                            bus.read_chr_mem(bank + ((tile_idx & 0xFE) + 1) * 16 + i);
-                    }
-                    self.dump_tile(
-                        sprite_palette,
-                        flip_horizontal,
-                        flip_vertical,
-                        tile,
-                        tile_x,
-                        tile_y,
-                    );
-
-                    for i in 0..16 {
-                        tile[i as usize] = bus.read_chr_mem(bank + (tile_idx & 0xFE) * 16 + i);
-                    }
-                    self.dump_tile(
-                        sprite_palette,
-                        flip_horizontal,
-                        flip_vertical,
-                        tile,
-                        tile_x,
-                        tile_y + 8,
-                    );
+                y - tile_y
+            };
+            // 8x16 sprites ignore PPUCTRL and pick their pattern table with bit 0 of the tile
+            let tile_addr = if height == 8 {
+                self.ctrl_reg.sprite_pattern_base_addr() + tile_idx * 16
+            } else {
+                (tile_idx & 1) * 0x1000 + ((tile_idx & 0xFE) + row / 8) * 16
+            };
+            let tile_row = (
+                bus.read_chr_mem(tile_addr + row % 8),
+                bus.read_chr_mem(tile_addr + row % 8 + 8),
+            );
+
+            let sprite_palette = self.sprite_palette(attributes & 0b11);
+            for x in 0..8 {
+                let pat = tile_pattern(tile_row, x);
+                if pat == 0 {
+                    // transparent
+                    continue;
+                }
+
+                let pixel_x = usize::from(tile_x + if flip_horizontal { 7 - x } else { x });
+                if pixel_x >= FRAME_WIDTH {
+                    continue;
                 }
-            }
-        }
-    }
 
-    fn dump_tile(
-        &mut self,
-        sprite_palette: [u8; 4],
-        x_flip: bool,
-        y_flip: bool,
-        tile: [u8; 16],
-        tile_x: u16,
-        tile_y: u16,
-    ) {
-        let flip_vertical = if y_flip { |x| 7 - x } else { |x| x };
+                if pixel_x < 8 && !self.left_edge_visible(registers::MaskReg::LEFTMOST_8PXL_SPRITE)
+                {
+                    continue;
+                }
 
-        let flip_horizontal = if x_flip { |y| 7 - y } else { |y| y };
+                if covered[pixel_x] {
+                    continue;
+                }
+                covered[pixel_x] = true;
 
-        for y in 0..8 {
-            let byte_lo = tile[y as usize];
-            let byte_hi = tile[y as usize + 8];
-            for x in 0..8 {
-                let shift = 7 - x;
-                let pat_lo = (byte_lo >> shift) & 0b1;
-                let pat_hi = (byte_hi >> shift) & 0b1;
-                let pat = pat_hi << 1 | pat_lo;
-                if pat != 0 {
-                    // non-transparant
-                    let color = sprite_palette[pat as usize];
-                    self.set_pixel(
-                        tile_x + flip_horizontal(x),
-                        tile_y + flip_vertical(y),
-                        color,
-                    );
+                let idx = usize::from(y) * FRAME_WIDTH + pixel_x;
+                if !(behind_background && self.bg_opaque[idx]) {
+                    self.frame[idx] = sprite_palette[pat as usize];
                 }
             }
         }
@@ -527,11 +1042,30 @@ impl Ppu {
         ]
     }
 
-    fn set_pixel(&mut self, x: u16, y: u16, color: u8) {
+    fn set_bg_pixel(&mut self, x: u16, y: u16, pattern: u8, palette: [u8; 4]) {
+        // Clipped pixels show the backdrop color
+        let pattern =
+            if x < 8 && !self.left_edge_visible(registers::MaskReg::LEFTMOST_8PXL_BACKGROUND) {
+                0
+            } else {
+                pattern
+            };
+
         let idx = y as usize * FRAME_WIDTH + x as usize;
         if idx < self.frame.len() {
-            self.frame[idx] = color;
+            self.frame[idx] = palette[pattern as usize];
+            self.bg_opaque[idx] = pattern != 0;
+        }
+    }
+
+    /// Whether the leftmost 8 pixels of a layer are shown, `flag` being its PPUMASK bit
+    fn left_edge_visible(&self, flag: registers::MaskReg) -> bool {
+        #[cfg(feature = "debugger")]
+        if self.force_show_left_edge {
+            return true;
         }
+
+        self.mask_reg.contains(flag)
     }
 
     /// See: https://wiki.nesdev.com/w/index.php?title=PPU_OAM#Sprite_zero_hits
@@ -553,6 +1087,26 @@ impl Ppu {
     }
 }
 
+/// 2-bit pattern value of the pixel at column `fine_x` of a tile row
+fn tile_pattern((lo, hi): (u8, u8), fine_x: u16) -> u8 {
+    let shift = 7 - fine_x;
+    ((hi >> shift) & 0b1) << 1 | ((lo >> shift) & 0b1)
+}
+
+/// Index in the palette table of an address in the $3F00..$3FFF range
+fn palette_index(addr: u16) -> usize {
+    // $3F20..$3FFF mirrors $3F00..$3F1F
+    let idx = usize::from(addr & 0x1F);
+
+    // Mirror some specific addresses to $3F00/$3F04/$3F08/$3F0C
+    // (usually, used for transparency)
+    if idx & 0x13 == 0x10 {
+        idx - 0x10
+    } else {
+        idx
+    }
+}
+
 #[cfg(test)]
 pub mod test {
     use super::*;
@@ -577,6 +1131,12 @@ pub mod test {
         }
     }
 
+    fn chr_ram_rom() -> alloc::vec::Vec<u8> {
+        let mut rom = alloc::vec![0u8; 16 + 0x4000];
+        rom[..6].copy_from_slice(&[0x4E, 0x45, 0x53, 0x1A, 0x01, 0x00]);
+        rom
+    }
+
     fn mock_emu_horizontal() -> MockEmulator {
         mock_emu(ROM_HORIZONTAL)
     }
@@ -649,6 +1209,31 @@ pub mod test {
         assert_eq!(emu.ppu.read(&mut bus, 0x2007), 0x88);
     }
 
+    #[test]
+    fn name_tables_writes_step_32() {
+        let mut emu = mock_emu_horizontal();
+        let mut bus = borrow_ppu_bus!(emu);
+
+        emu.ppu.write(&mut bus, 0x2000, 0b100);
+
+        emu.ppu.write(&mut bus, 0x2006, 0x20);
+        emu.ppu.write(&mut bus, 0x2006, 0x05);
+
+        for (i, data) in [0x11, 0x22, 0x33].iter().enumerate() {
+            emu.ppu.write(&mut bus, 0x2007, *data);
+            assert_eq!(emu.ppu.addr_reg.get(), 0x2005 + 32 * (i as u16 + 1));
+        }
+
+        // Switching back to +1 mode
+        emu.ppu.write(&mut bus, 0x2000, 0b000);
+        emu.ppu.read(&mut bus, 0x2007);
+        assert_eq!(emu.ppu.addr_reg.get(), 0x2066);
+
+        assert_eq!(emu.name_tables[0x0005], 0x11);
+        assert_eq!(emu.name_tables[0x0025], 0x22);
+        assert_eq!(emu.name_tables[0x0045], 0x33);
+    }
+
     // Horizontal
     // [0x2000 A ] [0x2400 a ]
     // [0x2800 B ] [0x2C00 b ]
@@ -713,6 +1298,26 @@ pub mod test {
         assert_eq!(emu.ppu.read(&mut bus, 0x2007), 0x77);
     }
 
+    #[test]
+    fn name_tables_mirroring_override() {
+        let mut emu = mock_emu_horizontal();
+        emu.cartridge
+            .set_mirroring_override(Some(Mirroring::Vertical));
+        assert!(matches!(emu.cartridge.mirroring(), Mirroring::Vertical));
+        let mut bus = borrow_ppu_bus!(emu);
+
+        // A
+        emu.ppu.write(&mut bus, 0x2006, 0x20);
+        emu.ppu.write(&mut bus, 0x2006, 0x05);
+        emu.ppu.write(&mut bus, 0x2007, 0x66);
+
+        // a
+        emu.ppu.write(&mut bus, 0x2006, 0x28);
+        emu.ppu.write(&mut bus, 0x2006, 0x05);
+        emu.ppu.read(&mut bus, 0x2007);
+        assert_eq!(emu.ppu.read(&mut bus, 0x2007), 0x66);
+    }
+
     #[test]
     fn name_tables_mirroring() {
         let mut emu = mock_emu_horizontal();
@@ -762,6 +1367,282 @@ pub mod test {
         assert_eq!(emu.ppu.status_reg.read() >> 7, 0);
     }
 
+    #[test]
+    fn read_status_before_vblank_suppresses_flag_and_nmi() {
+        let mut emu = mock_emu_horizontal();
+        let mut bus = borrow_ppu_bus!(emu);
+
+        emu.ppu.write(&mut bus, 0x2000, 0x80);
+        emu.ppu.scanline = 240;
+        emu.ppu.cycle_count = 340;
+
+        assert_eq!(emu.ppu.read(&mut bus, 0x2002) >> 7, 0);
+        emu.ppu.clock(&mut bus);
+
+        assert_eq!(emu.ppu.scanline, 241);
+        assert_eq!(emu.ppu.status_reg.read() >> 7, 0);
+        assert!(!emu.ppu.take_vblank_nmi_set_state());
+    }
+
+    #[test]
+    fn read_status_at_vblank_suppresses_nmi() {
+        let mut emu = mock_emu_horizontal();
+        let mut bus = borrow_ppu_bus!(emu);
+
+        emu.ppu.write(&mut bus, 0x2000, 0x80);
+        emu.ppu.scanline = 240;
+        emu.ppu.cycle_count = 340;
+        emu.ppu.clock(&mut bus);
+
+        assert_eq!(emu.ppu.read(&mut bus, 0x2002) >> 7, 1);
+        assert!(!emu.ppu.take_vblank_nmi_set_state());
+    }
+
+    #[test]
+    fn read_status_after_vblank_keeps_nmi() {
+        let mut emu = mock_emu_horizontal();
+        let mut bus = borrow_ppu_bus!(emu);
+
+        emu.ppu.write(&mut bus, 0x2000, 0x80);
+        emu.ppu.scanline = 240;
+        emu.ppu.cycle_count = 340;
+        emu.ppu.clock(&mut bus);
+        emu.ppu.clock(&mut bus);
+        emu.ppu.clock(&mut bus);
+
+        assert_eq!(emu.ppu.read(&mut bus, 0x2002) >> 7, 1);
+        assert!(emu.ppu.take_vblank_nmi_set_state());
+    }
+
+    #[test]
+    fn enable_nmi_during_vblank_triggers_nmi() {
+        let mut emu = mock_emu_horizontal();
+        let mut bus = borrow_ppu_bus!(emu);
+
+        emu.ppu.scanline = 240;
+        emu.ppu.cycle_count = 340;
+        emu.ppu.clock(&mut bus);
+        assert!(!emu.ppu.take_vblank_nmi_set_state());
+
+        emu.ppu.write(&mut bus, 0x2000, 0x80);
+        assert!(emu.ppu.take_vblank_nmi_set_state());
+    }
+
+    #[cfg(feature = "debugger")]
+    #[test]
+    fn status_flags_dont_clear_vblank() {
+        let mut emu = mock_emu_horizontal();
+        let mut bus = borrow_ppu_bus!(emu);
+
+        emu.ppu.scanline = 240;
+        emu.ppu.cycle_count = 340;
+        emu.ppu.clock(&mut bus);
+
+        let flags = emu.ppu.status_flags();
+        assert!(flags.vblank);
+        assert!(!flags.sprite_zero_hit);
+        assert_eq!(emu.ppu.status_flags(), flags);
+        assert_eq!(emu.ppu.read(&mut bus, 0x2002) >> 7, 1);
+    }
+
+    #[test]
+    fn status_read_resets_shared_write_toggle() {
+        let mut emu = mock_emu_horizontal();
+        let mut bus = borrow_ppu_bus!(emu);
+
+        // The read makes the next PPUSCROLL write an X write again
+        emu.ppu.write(&mut bus, 0x2005, 0x7D);
+        assert!(emu.ppu.addr_reg.latch);
+        emu.ppu.read(&mut bus, 0x2002);
+        assert!(!emu.ppu.addr_reg.latch);
+        emu.ppu.write(&mut bus, 0x2005, 0x5E);
+        assert_eq!(emu.ppu.addr_reg.temp, 0x000B);
+        assert_eq!(emu.ppu.addr_reg.fine_x, 6);
+
+        // PPUSCROLL left the toggle set, so this is the second PPUADDR write
+        emu.ppu.write(&mut bus, 0x2006, 0x21);
+        assert!(!emu.ppu.addr_reg.latch);
+        assert_eq!(emu.ppu.addr_reg.temp, 0x0021);
+        assert_eq!(emu.ppu.addr_reg.get(), 0x0021);
+
+        // A read between the two PPUADDR writes restarts the address
+        emu.ppu.write(&mut bus, 0x2006, 0x23);
+        assert_eq!(emu.ppu.addr_reg.temp, 0x2321);
+        emu.ppu.read(&mut bus, 0x2002);
+        assert!(!emu.ppu.addr_reg.latch);
+        emu.ppu.write(&mut bus, 0x2006, 0x45);
+        assert_eq!(emu.ppu.addr_reg.temp, 0x0521);
+        assert_eq!(emu.ppu.addr_reg.get(), 0x0021);
+        emu.ppu.write(&mut bus, 0x2006, 0x67);
+        assert_eq!(emu.ppu.addr_reg.temp, 0x0567);
+        assert_eq!(emu.ppu.addr_reg.get(), 0x0567);
+    }
+
+    #[test]
+    fn writes_ignored_during_warmup() {
+        let mut emu = mock_emu_horizontal();
+        let mut bus = borrow_ppu_bus!(emu);
+        emu.ppu.reset();
+
+        emu.ppu.write(&mut bus, 0x2000, 0x80);
+        emu.ppu.write(&mut bus, 0x2001, 0x1E);
+        assert!(!emu
+            .ppu
+            .ctrl_reg
+            .contains(registers::ControlReg::GENERATE_NMI));
+        assert!(!emu.ppu.rendering_enabled());
+
+        // Warmup ends with the first vblank
+        emu.ppu.scanline = 260;
+        emu.ppu.cycle_count = 340;
+        emu.ppu.clock(&mut bus);
+        emu.ppu.write(&mut bus, 0x2000, 0x80);
+        emu.ppu.write(&mut bus, 0x2001, 0x1E);
+        assert!(emu
+            .ppu
+            .ctrl_reg
+            .contains(registers::ControlReg::GENERATE_NMI));
+        assert!(emu.ppu.rendering_enabled());
+    }
+
+    #[cfg(feature = "debugger")]
+    #[test]
+    fn ctrl_and_mask() {
+        let mut emu = mock_emu_horizontal();
+        let mut bus = borrow_ppu_bus!(emu);
+
+        emu.ppu.write(&mut bus, 0x2000, 0xA8);
+        emu.ppu.write(&mut bus, 0x2009, 0x1E); // Mirror of $2001
+        assert_eq!(emu.ppu.ctrl(), 0xA8);
+        assert_eq!(emu.ppu.mask(), 0x1E);
+    }
+
+    #[cfg(feature = "debugger")]
+    #[test]
+    fn sprite() {
+        let mut emu = mock_emu_horizontal();
+        let mut bus = borrow_ppu_bus!(emu);
+
+        emu.ppu.write(&mut bus, 0x2003, 0x04);
+        for &data in &[0x10, 0x21, 0xA2, 0x30] {
+            emu.ppu.write(&mut bus, 0x2004, data);
+        }
+
+        assert_eq!(
+            emu.ppu.sprite(1),
+            Sprite {
+                y: 0x10,
+                tile: 0x21,
+                attributes: 0xA2,
+                x: 0x30,
+                palette: 6,
+                behind_background: true,
+                flip_horizontal: false,
+                flip_vertical: true,
+            }
+        );
+        assert_eq!(emu.ppu.sprite(65), emu.ppu.sprite(1));
+    }
+
+    #[test]
+    fn toggle_nmi_during_vblank() {
+        let mut emu = mock_emu_horizontal();
+        let mut bus = borrow_ppu_bus!(emu);
+
+        emu.ppu.scanline = 240;
+        emu.ppu.cycle_count = 340;
+        emu.ppu.write(&mut bus, 0x2000, 0x80);
+        emu.ppu.clock(&mut bus);
+        emu.ppu.clock(&mut bus);
+        assert!(emu.ppu.take_vblank_nmi_set_state());
+
+        // Writing with NMI already enabled doesn't trigger another one
+        emu.ppu.write(&mut bus, 0x2000, 0x80);
+        assert!(!emu.ppu.take_vblank_nmi_set_state());
+
+        // Disabling then enabling it again does
+        emu.ppu.write(&mut bus, 0x2000, 0x00);
+        emu.ppu.write(&mut bus, 0x2000, 0x80);
+        assert!(emu.ppu.take_vblank_nmi_set_state());
+
+        // Not once the vblank flag was cleared by a status read
+        emu.ppu.write(&mut bus, 0x2000, 0x00);
+        emu.ppu.read(&mut bus, 0x2002);
+        emu.ppu.write(&mut bus, 0x2000, 0x80);
+        assert!(!emu.ppu.take_vblank_nmi_set_state());
+    }
+
+    #[test]
+    fn palette_mirrors_background_color() {
+        let mut emu = mock_emu_horizontal();
+        let mut bus = borrow_ppu_bus!(emu);
+
+        emu.ppu.write(&mut bus, 0x2006, 0x3F);
+        emu.ppu.write(&mut bus, 0x2006, 0x10);
+        emu.ppu.write(&mut bus, 0x2007, 0x2A);
+
+        emu.ppu.write(&mut bus, 0x2006, 0x3F);
+        emu.ppu.write(&mut bus, 0x2006, 0x00);
+        assert_eq!(emu.ppu.read(&mut bus, 0x2007), 0x2A);
+        assert_eq!(emu.ppu.backdrop_color(), 0x2A);
+
+        // $3F3C is a mirror of $3F1C, itself a mirror of $3F0C
+        emu.ppu.write(&mut bus, 0x2006, 0x3F);
+        emu.ppu.write(&mut bus, 0x2006, 0x3C);
+        emu.ppu.write(&mut bus, 0x2007, 0x16);
+
+        emu.ppu.write(&mut bus, 0x2006, 0x3F);
+        emu.ppu.write(&mut bus, 0x2006, 0x0C);
+        assert_eq!(emu.ppu.read(&mut bus, 0x2007), 0x16);
+
+        // Non-mirrored entries stay independent
+        emu.ppu.write(&mut bus, 0x2006, 0x3F);
+        emu.ppu.write(&mut bus, 0x2006, 0x11);
+        emu.ppu.write(&mut bus, 0x2007, 0x05);
+
+        emu.ppu.write(&mut bus, 0x2006, 0x3F);
+        emu.ppu.write(&mut bus, 0x2006, 0x01);
+        assert_ne!(emu.ppu.read(&mut bus, 0x2007), 0x05);
+    }
+
+    #[test]
+    fn frame_cropped() {
+        let mut frame = PpuFrame::default();
+        frame[FRAME_WIDTH * 8 + 8] = 0x01;
+        frame[FRAME_WIDTH * 9 + 247] = 0x02;
+
+        let overscan = Overscan {
+            top: 8,
+            bottom: 8,
+            left: 8,
+            right: 8,
+        };
+        let cropped: alloc::vec::Vec<u8> = frame.cropped(overscan).collect();
+
+        assert_eq!(cropped.len(), 240 * 224);
+        assert_eq!(cropped[0], 0x01);
+        assert_eq!(cropped[240 + 239], 0x02);
+        assert_eq!(cropped.iter().filter(|p| **p != 0).count(), 2);
+    }
+
+    #[test]
+    fn frame_diff() {
+        let a = PpuFrame::default();
+        let mut b = PpuFrame::default();
+        assert_eq!(a.diff(&b), None);
+
+        b[FRAME_WIDTH * 10 + 20] = 0x0F;
+        b[FRAME_WIDTH * 30 + 5] = 0x0F;
+        assert_eq!(
+            a.diff(&b),
+            Some(FrameDiff {
+                count: 2,
+                top_left: (5, 10),
+                bottom_right: (20, 30),
+            })
+        );
+    }
+
     #[test]
     fn oam_read_write() {
         let mut emu = mock_emu_horizontal();
@@ -794,4 +1675,495 @@ pub mod test {
         emu.ppu.write(&mut bus, 0x2003, 0x0F); // "wrap around"
         assert_eq!(emu.ppu.read(&mut bus, 0x2004), 0x88);
     }
+
+    #[cfg(feature = "debugger")]
+    #[test]
+    fn vram_access_bypasses_registers() {
+        let mut emu = mock_emu_horizontal();
+        let mut bus = borrow_ppu_bus!(emu);
+
+        emu.ppu.write(&mut bus, 0x2006, 0x21);
+        emu.ppu.write(&mut bus, 0x2006, 0x00);
+
+        // Horizontal mirroring: $2400 is a mirror of $2000
+        emu.ppu.vram_write(&mut bus, 0x2400, 0x42);
+        assert_eq!(emu.ppu.vram_read(&mut bus, 0x2000), 0x42);
+        emu.ppu.vram_write(&mut bus, 0x3F10, 0x0F);
+        assert_eq!(emu.ppu.vram_read(&mut bus, 0x3F00), 0x0F);
+
+        // Address latch was left untouched
+        assert_eq!(emu.ppu.addr_reg.get(), 0x2100);
+    }
+
+    #[test]
+    fn fast_accuracy_renders_same_background() {
+        let rom = chr_ram_rom();
+
+        let render = |accuracy: Accuracy| {
+            let mut emu = mock_emu(&rom);
+            emu.ppu.set_accuracy(accuracy);
+            let mut bus = borrow_ppu_bus!(emu);
+
+            // Tile 1 is a vertical stripe pattern
+            emu.ppu.write(&mut bus, 0x2006, 0x00);
+            emu.ppu.write(&mut bus, 0x2006, 0x10);
+            for data in [0xAA; 8].iter().chain([0xCC; 8].iter()) {
+                emu.ppu.write(&mut bus, 0x2007, *data);
+            }
+
+            // Checkerboard of tiles 0 and 1
+            emu.ppu.write(&mut bus, 0x2006, 0x20);
+            emu.ppu.write(&mut bus, 0x2006, 0x00);
+            for i in 0..0x3C0 {
+                emu.ppu.write(&mut bus, 0x2007, (i % 2) as u8);
+            }
+
+            emu.ppu.write(&mut bus, 0x2006, 0x3F);
+            emu.ppu.write(&mut bus, 0x2006, 0x00);
+            for color in &[0x0F, 0x16, 0x27, 0x18] {
+                emu.ppu.write(&mut bus, 0x2007, *color);
+            }
+
+            // Fine horizontal scroll. PPUADDR writes moved the nametable, reset it too.
+            emu.ppu.write(&mut bus, 0x2000, 0x00);
+            emu.ppu.write(&mut bus, 0x2005, 0x03);
+            emu.ppu.write(&mut bus, 0x2005, 0x00);
+
+            for _ in 0..(341 * 262) {
+                emu.ppu.clock(&mut bus);
+            }
+            emu.ppu.frame().clone()
+        };
+
+        let accurate = render(Accuracy::CycleAccurate);
+        let fast = render(Accuracy::Fast);
+        assert!(accurate.contains(&0x18));
+        assert_eq!(accurate.diff(&fast), None);
+    }
+
+    #[cfg(feature = "debugger")]
+    #[test]
+    fn write_callback() {
+        use alloc::sync::Arc;
+        use core::sync::atomic::{AtomicU32, Ordering};
+
+        let mut emu = mock_emu_horizontal();
+        let mut bus = borrow_ppu_bus!(emu);
+        for _ in 0..(341 * 3 + 10) {
+            emu.ppu.clock(&mut bus);
+        }
+
+        let writes = Arc::new(AtomicU32::new(0));
+        let last = Arc::new(AtomicU32::new(0));
+        let position = Arc::new(AtomicU32::new(0));
+        let (w, l, p) = (writes.clone(), last.clone(), position.clone());
+        emu.ppu
+            .set_write_callback(Some(Box::new(move |addr, data, scanline, cycle| {
+                w.fetch_add(1, Ordering::Relaxed);
+                l.store(u32::from(addr) << 8 | u32::from(data), Ordering::Relaxed);
+                p.store(
+                    (scanline as u32) << 16 | u32::from(cycle),
+                    Ordering::Relaxed,
+                );
+            })));
+
+        // $3456 mirrors PPUADDR
+        emu.ppu.notify_register_write(0x3456, 0x21);
+        assert_eq!(writes.load(Ordering::Relaxed), 1);
+        assert_eq!(last.load(Ordering::Relaxed), 0x20_06_21);
+        assert_eq!(
+            position.load(Ordering::Relaxed),
+            (emu.ppu.scanline as u32) << 16 | u32::from(emu.ppu.cycle_count)
+        );
+
+        emu.ppu.clock(&mut bus);
+        emu.ppu.notify_register_write(0x2000, 0x80);
+        assert_eq!(writes.load(Ordering::Relaxed), 2);
+        assert_eq!(last.load(Ordering::Relaxed), 0x20_00_80);
+        assert_eq!(
+            position.load(Ordering::Relaxed),
+            (emu.ppu.scanline as u32) << 16 | u32::from(emu.ppu.cycle_count)
+        );
+
+        emu.ppu.set_write_callback(None);
+        emu.ppu.notify_register_write(0x2001, 0x1E);
+        assert_eq!(writes.load(Ordering::Relaxed), 2);
+    }
+
+    #[cfg(feature = "debugger")]
+    #[test]
+    fn sprite_counts_per_scanline() {
+        let mut emu = mock_emu_horizontal();
+        let mut bus = borrow_ppu_bus!(emu);
+
+        // 9 sprites at Y=10 and one at Y=20, the rest is hidden below the screen
+        let mut oam = [0xFFu8; 256];
+        for sprite in 0..10 {
+            oam[sprite * 4] = if sprite < 9 { 10 } else { 20 };
+        }
+        emu.ppu.write_oam_dma(&oam);
+
+        for _ in 0..(341 * 30) {
+            emu.ppu.clock(&mut bus);
+        }
+
+        assert_eq!(emu.ppu.sprites_on_scanline(9), 0);
+        assert_eq!(emu.ppu.sprites_on_scanline(10), 9);
+        assert_eq!(emu.ppu.sprites_on_scanline(17), 9);
+        assert_eq!(emu.ppu.sprites_on_scanline(18), 0);
+        assert_eq!(emu.ppu.sprites_on_scanline(20), 1);
+        assert_eq!(emu.ppu.sprites_on_scanline(-1), 0);
+    }
+
+    #[cfg(feature = "debugger")]
+    #[test]
+    fn sprite_counts_match_rendering() {
+        let rom = chr_ram_rom();
+        let mut emu = mock_emu(&rom);
+        let mut bus = borrow_ppu_bus!(emu);
+
+        // Tile 2: fully opaque
+        emu.ppu.write(&mut bus, 0x2006, 0x00);
+        emu.ppu.write(&mut bus, 0x2006, 0x20);
+        for _ in 0..16 {
+            emu.ppu.write(&mut bus, 0x2007, 0xFF);
+        }
+        emu.ppu.write(&mut bus, 0x2006, 0x3F);
+        emu.ppu.write(&mut bus, 0x2006, 0x13);
+        emu.ppu.write(&mut bus, 0x2007, 0x2A);
+
+        // Sprites only
+        emu.ppu.write(&mut bus, 0x2001, 0x14);
+        let mut oam = [0xFFu8; 256];
+        oam[..4].copy_from_slice(&[20, 2, 0x00, 40]);
+        emu.ppu.write_oam_dma(&oam);
+
+        for _ in 0..(341 * 262) {
+            emu.ppu.clock(&mut bus);
+        }
+
+        let frame = emu.ppu.frame().clone();
+        for line in 0..FRAME_HEIGHT {
+            let drawn = frame[line * FRAME_WIDTH + 40] == 0x2A;
+            assert_eq!(emu.ppu.sprites_on_scanline(line as i16) == 1, drawn);
+        }
+        assert!(frame.contains(&0x2A));
+    }
+
+    #[test]
+    fn frame_to_rgba() {
+        let mut frame = PpuFrame::default();
+        frame[1] = 0x01;
+        frame[2] = 0x41; // Only 6 bits are used
+
+        let mut buffer = alloc::vec![0u8; RGBA_FRAME_SIZE];
+        frame.to_rgba(&mut buffer).unwrap();
+        assert_eq!(
+            buffer[..12],
+            [0x7C, 0x7C, 0x7C, 0xFF, 0x00, 0x00, 0xFC, 0xFF, 0x00, 0x00, 0xFC, 0xFF]
+        );
+
+        assert_eq!(
+            frame.to_rgba(&mut buffer[4..]),
+            Err(BufferSizeError {
+                expected: RGBA_FRAME_SIZE,
+                actual: RGBA_FRAME_SIZE - 4
+            })
+        );
+    }
+
+    #[test]
+    fn frame_to_luma() {
+        let mut frame = PpuFrame::default();
+        frame[1] = 0x20; // White
+        frame[2] = 0x01; // Blue
+
+        let luma = frame.to_luma();
+        assert_eq!(luma.len(), FRAME_WIDTH * FRAME_HEIGHT);
+        assert_eq!(luma[..3], [0x7C, 0xF8, 0x1C]);
+    }
+
+    #[test]
+    fn read_write_only_registers_returns_open_bus() {
+        let mut emu = mock_emu_horizontal();
+        let mut bus = borrow_ppu_bus!(emu);
+
+        for addr in &[0x2000, 0x2001, 0x2003, 0x2005, 0x2006, 0x3FF8] {
+            emu.ppu.write(&mut bus, 0x2003, 0x5A);
+            assert_eq!(emu.ppu.read(&mut bus, *addr), 0x5A);
+        }
+
+        // Readable registers refresh the latch too
+        emu.ppu.write(&mut bus, 0x2003, 0x00);
+        emu.ppu.write(&mut bus, 0x2004, 0x3C);
+        emu.ppu.write(&mut bus, 0x2003, 0x00);
+        emu.ppu.write(&mut bus, 0x2001, 0x00);
+        assert_eq!(emu.ppu.read(&mut bus, 0x2004), 0x3C);
+        assert_eq!(emu.ppu.read(&mut bus, 0x2005), 0x3C);
+    }
+
+    #[test]
+    fn rendering_enabled() {
+        let mut emu = mock_emu_horizontal();
+        let mut bus = borrow_ppu_bus!(emu);
+
+        assert!(!emu.ppu.rendering_enabled());
+        emu.ppu.write(&mut bus, 0x2001, 0x1E);
+        assert!(emu.ppu.rendering_enabled());
+        emu.ppu.write(&mut bus, 0x2001, 0x10);
+        assert!(emu.ppu.rendering_enabled());
+        emu.ppu.write(&mut bus, 0x2001, 0x06);
+        assert!(!emu.ppu.rendering_enabled());
+    }
+
+    #[test]
+    fn status_low_bits_are_open_bus() {
+        let mut emu = mock_emu_horizontal();
+        let mut bus = borrow_ppu_bus!(emu);
+
+        emu.ppu
+            .status_reg
+            .set(registers::StatusReg::VBLANK_STARTED, true);
+        emu.ppu.write(&mut bus, 0x2003, 0xFF);
+        assert_eq!(emu.ppu.read(&mut bus, 0x2002), 0x9F);
+
+        // The status read itself refreshes the latch
+        assert_eq!(emu.ppu.read(&mut bus, 0x2002), 0x1F);
+    }
+
+    #[test]
+    fn clock_no_render_keeps_timing() {
+        let mut emu = mock_emu_horizontal();
+        let mut bus = borrow_ppu_bus!(emu);
+
+        emu.ppu.write(&mut bus, 0x2006, 0x3F);
+        emu.ppu.write(&mut bus, 0x2006, 0x00);
+        emu.ppu.write(&mut bus, 0x2007, 0x21);
+        emu.ppu.write(&mut bus, 0x2000, 0x80); // Generate NMI
+
+        for _ in 0..(341 * 242) {
+            emu.ppu.clock_no_render(&mut bus);
+        }
+
+        assert!(emu.ppu.take_vblank_nmi_set_state());
+        assert_eq!(emu.ppu.frame().diff(&PpuFrame::default()), None);
+    }
+
+    #[test]
+    fn sprite_priority() {
+        let rom = chr_ram_rom();
+        let mut emu = mock_emu(&rom);
+        let mut bus = borrow_ppu_bus!(emu);
+
+        // Tile 1: left half opaque, tile 2: fully opaque
+        emu.ppu.write(&mut bus, 0x2006, 0x00);
+        emu.ppu.write(&mut bus, 0x2006, 0x10);
+        for data in [0xF0; 8].iter().chain([0x00; 8].iter()) {
+            emu.ppu.write(&mut bus, 0x2007, *data);
+        }
+        for _ in 0..16 {
+            emu.ppu.write(&mut bus, 0x2007, 0xFF);
+        }
+
+        // Background made of tile 1
+        emu.ppu.write(&mut bus, 0x2006, 0x20);
+        emu.ppu.write(&mut bus, 0x2006, 0x00);
+        for _ in 0..32 {
+            emu.ppu.write(&mut bus, 0x2007, 0x01);
+        }
+
+        emu.ppu.write(&mut bus, 0x2006, 0x3F);
+        emu.ppu.write(&mut bus, 0x2006, 0x01);
+        emu.ppu.write(&mut bus, 0x2007, 0x16); // Background color 1
+        emu.ppu.write(&mut bus, 0x2006, 0x3F);
+        emu.ppu.write(&mut bus, 0x2006, 0x13);
+        emu.ppu.write(&mut bus, 0x2007, 0x2A); // Sprite palette 0 color 3
+        emu.ppu.write(&mut bus, 0x2006, 0x3F);
+        emu.ppu.write(&mut bus, 0x2006, 0x17);
+        emu.ppu.write(&mut bus, 0x2007, 0x11); // Sprite palette 1 color 3
+
+        // PPUADDR writes moved the scroll, reset it like games do
+        emu.ppu.write(&mut bus, 0x2000, 0x00);
+        emu.ppu.write(&mut bus, 0x2005, 0x00);
+        emu.ppu.write(&mut bus, 0x2005, 0x00);
+
+        // Show the leftmost 8 pixels
+        emu.ppu.write(&mut bus, 0x2001, 0x1E);
+
+        let mut oam = [0xFFu8; 256];
+        oam[..12].copy_from_slice(&[
+            0, 2, 0x20, 0, // Behind background
+            0, 2, 0x00, 8, // In front of background
+            0, 2, 0x01, 0, // In front, but under sprite 0
+        ]);
+        emu.ppu.write_oam_dma(&oam);
+
+        for _ in 0..(341 * 262) {
+            emu.ppu.clock(&mut bus);
+        }
+
+        let frame = emu.ppu.frame();
+        assert_eq!(frame[0], 0x16); // Opaque background wins over a back sprite
+        assert_eq!(frame[4], 0x2A); // Transparent background shows the back sprite
+        assert_eq!(frame[8], 0x2A); // Front sprite covers opaque background
+        assert_eq!(frame[12], 0x2A);
+    }
+
+    #[test]
+    fn tall_sprite_pattern_table() {
+        let rom = chr_ram_rom();
+        let mut emu = mock_emu(&rom);
+        let mut bus = borrow_ppu_bus!(emu);
+
+        // Tiles $1020 and $1030: color 1 on top, color 3 at the bottom.
+        // The same tiles at $0000 are transparent.
+        emu.ppu.write(&mut bus, 0x2006, 0x10);
+        emu.ppu.write(&mut bus, 0x2006, 0x20);
+        for data in [0xFF; 8].iter().chain([0x00; 8].iter()) {
+            emu.ppu.write(&mut bus, 0x2007, *data);
+        }
+        for _ in 0..16 {
+            emu.ppu.write(&mut bus, 0x2007, 0xFF);
+        }
+
+        emu.ppu.write(&mut bus, 0x2006, 0x3F);
+        emu.ppu.write(&mut bus, 0x2006, 0x11);
+        for color in &[0x11, 0x21, 0x2A] {
+            emu.ppu.write(&mut bus, 0x2007, *color);
+        }
+
+        // 8x16 sprites with PPUCTRL pointing sprites to $0000, tile 3 selects $1000
+        emu.ppu.write(&mut bus, 0x2000, 0x20);
+        emu.ppu.write(&mut bus, 0x2005, 0x00);
+        emu.ppu.write(&mut bus, 0x2005, 0x00);
+        emu.ppu.write(&mut bus, 0x2001, 0x1E);
+
+        let mut oam = [0xFFu8; 256];
+        oam[..4].copy_from_slice(&[0, 3, 0x00, 0]);
+        emu.ppu.write_oam_dma(&oam);
+
+        for _ in 0..(341 * 262) {
+            emu.ppu.clock(&mut bus);
+        }
+
+        let frame = emu.ppu.frame();
+        assert_eq!(frame[FRAME_WIDTH + 4], 0x11);
+        assert_eq!(frame[8 * FRAME_WIDTH + 4], 0x2A);
+    }
+
+    #[test]
+    fn mid_frame_chr_bank_switch() {
+        // CNROM with 2 CHR banks, tile 1 uses color 1 in bank 0 and color 3 in bank 1
+        let mut rom = alloc::vec![0u8; 16 + 0x4000 + 0x4000];
+        rom[..7].copy_from_slice(&[0x4E, 0x45, 0x53, 0x1A, 0x01, 0x02, 0x30]);
+        let chr = 16 + 0x4000;
+        rom[chr + 0x10..chr + 0x18].copy_from_slice(&[0xFF; 8]);
+        rom[chr + 0x2010..chr + 0x2020].copy_from_slice(&[0xFF; 16]);
+
+        let mut emu = mock_emu(&rom);
+        let mut bus = borrow_ppu_bus!(emu);
+
+        // Background made of tile 1
+        emu.ppu.write(&mut bus, 0x2006, 0x20);
+        emu.ppu.write(&mut bus, 0x2006, 0x00);
+        for _ in 0..0x3C0 {
+            emu.ppu.write(&mut bus, 0x2007, 0x01);
+        }
+
+        emu.ppu.write(&mut bus, 0x2006, 0x3F);
+        emu.ppu.write(&mut bus, 0x2006, 0x01);
+        for color in &[0x16, 0x27, 0x18] {
+            emu.ppu.write(&mut bus, 0x2007, *color);
+        }
+        emu.ppu.write(&mut bus, 0x2006, 0x3F);
+        emu.ppu.write(&mut bus, 0x2006, 0x11);
+        for color in &[0x11, 0x21, 0x31] {
+            emu.ppu.write(&mut bus, 0x2007, *color);
+        }
+
+        // A sprite in each half of the screen
+        let mut oam = [0xFFu8; 256];
+        oam[..8].copy_from_slice(&[50, 1, 0x00, 100, 150, 1, 0x00, 100]);
+        emu.ppu.write_oam_dma(&oam);
+        // PPUADDR writes moved the scroll, reset it like games do
+        emu.ppu.write(&mut bus, 0x2000, 0x00);
+        emu.ppu.write(&mut bus, 0x2005, 0x00);
+        emu.ppu.write(&mut bus, 0x2005, 0x00);
+        emu.ppu.write(&mut bus, 0x2001, 0x1E);
+
+        for _ in 0..(341 * 100) {
+            emu.ppu.clock(&mut bus);
+        }
+
+        // Switch to CHR bank 1 from line 100
+        emu.cartridge.write_prg_mem(0x8000, 0x01);
+        let mut bus = borrow_ppu_bus!(emu);
+        for _ in 0..(341 * 162) {
+            emu.ppu.clock(&mut bus);
+        }
+
+        let frame = emu.ppu.frame();
+        assert_eq!(frame[50 * FRAME_WIDTH], 0x16);
+        assert_eq!(frame[50 * FRAME_WIDTH + 100], 0x11);
+        assert_eq!(frame[150 * FRAME_WIDTH], 0x18);
+        assert_eq!(frame[150 * FRAME_WIDTH + 100], 0x31);
+    }
+
+    #[test]
+    fn left_edge_clipping() {
+        let rom = chr_ram_rom();
+
+        let render = |mask: u8| {
+            let mut emu = mock_emu(&rom);
+            let mut bus = borrow_ppu_bus!(emu);
+
+            // Tile 1 is fully opaque
+            emu.ppu.write(&mut bus, 0x2006, 0x00);
+            emu.ppu.write(&mut bus, 0x2006, 0x10);
+            for _ in 0..16 {
+                emu.ppu.write(&mut bus, 0x2007, 0xFF);
+            }
+
+            emu.ppu.write(&mut bus, 0x2006, 0x20);
+            emu.ppu.write(&mut bus, 0x2006, 0x00);
+            for _ in 0..32 {
+                emu.ppu.write(&mut bus, 0x2007, 0x01);
+            }
+
+            emu.ppu.write(&mut bus, 0x2006, 0x3F);
+            emu.ppu.write(&mut bus, 0x2006, 0x00);
+            for color in &[0x0F, 0x16, 0x27, 0x18] {
+                emu.ppu.write(&mut bus, 0x2007, *color);
+            }
+            emu.ppu.write(&mut bus, 0x2006, 0x3F);
+            emu.ppu.write(&mut bus, 0x2006, 0x13);
+            emu.ppu.write(&mut bus, 0x2007, 0x2A);
+
+            // Sprite straddling the left edge, on the line below the background row
+            let mut oam = [0xFFu8; 256];
+            oam[..4].copy_from_slice(&[8, 1, 0x00, 4]);
+            emu.ppu.write_oam_dma(&oam);
+
+            // PPUADDR writes moved the scroll, reset it like games do
+            emu.ppu.write(&mut bus, 0x2000, 0x00);
+            emu.ppu.write(&mut bus, 0x2005, 0x00);
+            emu.ppu.write(&mut bus, 0x2005, 0x00);
+            emu.ppu.write(&mut bus, 0x2001, mask);
+            for _ in 0..(341 * 262) {
+                emu.ppu.clock(&mut bus);
+            }
+            emu.ppu.frame().clone()
+        };
+
+        let shown = render(0x1E);
+        assert_eq!(shown[0], 0x18);
+        assert_eq!(shown[8 * FRAME_WIDTH + 4], 0x2A);
+
+        let clipped = render(0x18);
+        assert_eq!(clipped[0], 0x0F);
+        assert_eq!(clipped[7], 0x0F);
+        assert_eq!(clipped[8], 0x18);
+        assert_eq!(clipped[8 * FRAME_WIDTH + 7], 0x0F);
+        assert_eq!(clipped[8 * FRAME_WIDTH + 8], 0x2A);
+    }
 }
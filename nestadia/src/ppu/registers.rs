@@ -2,35 +2,60 @@ use bitflags::bitflags;
 
 // == VRAM address register == //
 
-/// VRAM address (0x0000..0x3FFF)
-/// http://wiki.nesdev.com/w/index.php/PPU_registers#PPUADDR
+/// Internal registers shared by PPUSCROLL and PPUADDR ("loopy" v, t, x and w)
+/// http://wiki.nesdev.com/w/index.php/PPU_scrolling#PPU_internal_registers
+///
+/// `t` holds the scroll position: coarse X in bits 0-4, coarse Y in bits 5-9,
+/// the nametable in bits 10-11 and fine Y in bits 12-14.
 pub struct VramAddr {
-    value: u16,
-    latch: bool,
+    value: u16,             // v: VRAM address (0x0000..0x3FFF) used by PPUDATA
+    pub(super) temp: u16,   // t: scroll position, or address being written in PPUADDR
+    pub(super) fine_x: u8,  // x: fine horizontal scroll
+    pub(super) latch: bool, // w: second write toggle
 }
 
 impl Default for VramAddr {
     fn default() -> Self {
         Self {
             value: 0,
+            temp: 0,
+            fine_x: 0,
             latch: false,
         }
     }
 }
 
 impl VramAddr {
+    /// Write on PPUADDR. The address is only used once both bytes are written.
     pub fn load(&mut self, data: u8) {
         if self.latch {
-            // reset and update lower
-            self.value &= 0xFF00;
-            self.value |= u16::from(data);
+            // update lower byte and copy t to v
+            self.temp = (self.temp & 0xFF00) | u16::from(data);
+            self.value = self.temp;
+            self.mirror();
         } else {
-            // reset and update higher
-            self.value &= 0x00FF;
-            self.value |= u16::from(data) << 8;
+            // update higher byte, bit 14 is cleared
+            self.temp = (self.temp & 0x00FF) | (u16::from(data & 0x3F) << 8);
+        }
+        self.latch = !self.latch;
+    }
+
+    /// Write on PPUSCROLL: X then Y
+    pub fn write_scroll(&mut self, data: u8) {
+        if self.latch {
+            self.temp = (self.temp & !0x73E0)
+                | (u16::from(data & 0x07) << 12)
+                | (u16::from(data & 0xF8) << 2);
+        } else {
+            self.temp = (self.temp & !0x001F) | u16::from(data >> 3);
+            self.fine_x = data & 0x07;
         }
         self.latch = !self.latch;
-        self.mirror();
+    }
+
+    /// Write on PPUCTRL, whose bits 0-1 select the nametable
+    pub fn write_nametable(&mut self, data: u8) {
+        self.temp = (self.temp & !0x0C00) | (u16::from(data & 0x03) << 10);
     }
 
     pub fn get(&self) -> u16 {
@@ -46,6 +71,18 @@ impl VramAddr {
         self.latch = false;
     }
 
+    pub fn scroll_x(&self) -> u8 {
+        ((self.temp & 0x001F) as u8) << 3 | self.fine_x
+    }
+
+    pub fn scroll_y(&self) -> u8 {
+        (((self.temp >> 5) & 0x001F) as u8) << 3 | ((self.temp >> 12) & 0x07) as u8
+    }
+
+    pub fn nametable_base_addr(&self) -> u16 {
+        0x2000 | (self.temp & 0x0C00)
+    }
+
     fn mirror(&mut self) {
         const MIRRORING_HIGHER_BOUND: u16 = 0x3FFF;
         self.value &= MIRRORING_HIGHER_BOUND;
@@ -110,16 +147,6 @@ impl ControlReg {
         }
     }
 
-    pub fn nametable_base_addr(&self) -> u16 {
-        match self.bits & 0b11 {
-            0 => 0x2000,
-            1 => 0x2400,
-            2 => 0x2800,
-            3 => 0x2c00,
-            _ => unreachable!(),
-        }
-    }
-
     pub fn sprite_pattern_base_addr(&self) -> u16 {
         if self.contains(Self::SPRITE_PATTERN_ADDR) {
             0x1000
@@ -155,48 +182,6 @@ impl ControlReg {
     }
 }
 
-// == scroll register == //
-
-/// http://wiki.nesdev.com/w/index.php/PPU_registers#PPUSCROLL
-pub struct ScrollReg {
-    scroll_x: u8,
-    scroll_y: u8,
-    latch: bool,
-}
-
-impl Default for ScrollReg {
-    fn default() -> Self {
-        ScrollReg {
-            scroll_x: 0,
-            scroll_y: 0,
-            latch: false,
-        }
-    }
-}
-
-impl ScrollReg {
-    pub fn write(&mut self, data: u8) {
-        if self.latch {
-            self.scroll_y = data;
-        } else {
-            self.scroll_x = data;
-        }
-        self.latch = !self.latch;
-    }
-
-    pub fn reset_latch(&mut self) {
-        self.latch = false;
-    }
-
-    pub fn scroll_x(&self) -> u8 {
-        self.scroll_x
-    }
-
-    pub fn scroll_y(&self) -> u8 {
-        self.scroll_y
-    }
-}
-
 // == status register == //
 
 bitflags! {
@@ -288,6 +273,7 @@ mod test {
         let mut reg = VramAddr {
             value: 0b1001_1110_1111_1111,
             latch: true,
+            ..VramAddr::default()
         };
         reg.mirror();
         assert_eq!(reg.get(), 0b0001_1110_1111_1111);
@@ -297,15 +283,38 @@ mod test {
     fn vram_addr_load() {
         let mut reg = VramAddr::default();
         reg.load(0xAC);
-        assert_eq!(reg.get(), 0x2C00);
+        assert_eq!(reg.temp, 0x2C00);
+        assert_eq!(reg.get(), 0x0000); // v is only updated by the second write
         reg.load(0x5F);
         assert_eq!(reg.get(), 0x2C5F);
         reg.load(0x06);
-        assert_eq!(reg.get(), 0x065F);
+        assert_eq!(reg.temp, 0x065F);
+        assert_eq!(reg.get(), 0x2C5F);
         reg.load(0x00);
         assert_eq!(reg.get(), 0x0600);
     }
 
+    #[test]
+    fn scroll_and_addr_share_registers() {
+        let mut reg = VramAddr::default();
+        reg.write_nametable(0x01);
+        reg.write_scroll(0x7D); // X: coarse 15, fine 5
+        reg.write_scroll(0x5E); // Y: coarse 11, fine 6
+        assert_eq!(reg.temp, 0x656F); // Fine Y 6, nametable 1, coarse Y 11, coarse X 15
+        assert_eq!((reg.scroll_x(), reg.scroll_y()), (0x7D, 0x5E));
+        assert_eq!(reg.nametable_base_addr(), 0x2400);
+
+        // The first PPUADDR write overwrites fine Y, the nametable and coarse Y bits 3-4
+        reg.load(0x08);
+        assert_eq!(reg.nametable_base_addr(), 0x2800);
+        assert_eq!(reg.scroll_y(), 0x18);
+
+        // The second PPUADDR write sets coarse X and the low bits of coarse Y
+        reg.load(0x63);
+        assert_eq!(reg.get(), 0x0863);
+        assert_eq!((reg.scroll_x(), reg.scroll_y()), (0x1D, 0x18));
+    }
+
     #[test]
     fn vram_addr_inc() {
         let mut reg = VramAddr::default();
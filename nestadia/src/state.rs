@@ -0,0 +1,31 @@
+/// Errors returned when a save state can't be restored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateError {
+    /// The state was written by a newer or unknown version of the format
+    UnsupportedVersion(u8),
+    /// The state is shorter than its format requires (sizes in bytes)
+    Truncated { expected: usize, got: usize },
+    /// The state was saved with a different ROM than the loaded one
+    RomMismatch,
+    /// The state is malformed, with a description of the invalid part
+    Corrupt(&'static str),
+}
+
+impl core::fmt::Display for StateError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            StateError::UnsupportedVersion(version) => write!(
+                f,
+                "save state format version {} is not supported by this version of nestadia",
+                version
+            ),
+            StateError::Truncated { expected, got } => write!(
+                f,
+                "save state is truncated: expected {} bytes, got {}",
+                expected, got
+            ),
+            StateError::RomMismatch => write!(f, "save state belongs to a different ROM"),
+            StateError::Corrupt(reason) => write!(f, "save state is corrupt: {}", reason),
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use alloc::vec::Vec;
+
+use crate::cpu::disassembler::Instruction;
+
+/// CPU state right before an instruction was executed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub pc: u16,
+    pub instruction: Instruction,
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub st: u8,
+    /// Raw status register (P)
+    pub status: u8,
+    /// CPU cycles elapsed when the instruction started
+    pub cycle: u64,
+}
+
+/// Interrupt or DMA, with the PPU position when it happened
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameEvent {
+    pub kind: FrameEventKind,
+    pub scanline: i16,
+    pub cycle: u16,
+}
+
+/// APU IRQs and DMC DMA are missing since the APU is not emulated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameEventKind {
+    /// NMI taken by the CPU, raised by the PPU at vblank
+    Nmi,
+    /// IRQ taken by the CPU, raised by the mapper
+    MapperIrq,
+    /// OAM DMA started by a write on $4014
+    OamDma,
+}
+
+/// Events of the frame being run, and of the last completed one
+#[derive(Default)]
+pub struct FrameEvents {
+    current: Vec<FrameEvent>,
+    last: Vec<FrameEvent>,
+}
+
+impl FrameEvents {
+    pub fn push(&mut self, event: FrameEvent) {
+        self.current.push(event);
+    }
+
+    /// Keeps the events of the completed frame and starts a new one, reusing the buffers
+    pub fn end_frame(&mut self) {
+        core::mem::swap(&mut self.current, &mut self.last);
+        self.current.clear();
+    }
+
+    pub fn last_frame(&self) -> &[FrameEvent] {
+        &self.last
+    }
+}
+
+/// Keeps the last `capacity` entries, oldest first
+pub struct TraceHistory {
+    capacity: usize,
+    // Room for twice the capacity, so the newest entries can always be borrowed as one
+    // slice. Once full, the newest half is moved to the front, which doesn't reallocate.
+    entries: Vec<TraceEntry>,
+}
+
+impl TraceHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Vec::with_capacity(capacity * 2),
+        }
+    }
+
+    pub fn push(&mut self, entry: TraceEntry) {
+        if self.entries.len() == self.capacity * 2 {
+            self.entries.copy_within(self.capacity.., 0);
+            self.entries.truncate(self.capacity);
+        }
+        self.entries.push(entry);
+    }
+
+    pub fn entries(&self) -> &[TraceEntry] {
+        let start = self.entries.len().saturating_sub(self.capacity);
+        &self.entries[start..]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entry(pc: u16) -> TraceEntry {
+        TraceEntry {
+            pc,
+            instruction: Instruction::decode([0xEA, 0, 0]),
+            a: 0,
+            x: 0,
+            y: 0,
+            st: 0xFD,
+            status: 0x24,
+            cycle: 0,
+        }
+    }
+
+    #[test]
+    fn keeps_newest_entries() {
+        let mut history = TraceHistory::new(3);
+        history.push(entry(0));
+        history.push(entry(1));
+        assert_eq!(history.entries(), &[entry(0), entry(1)]);
+
+        for pc in 2..10 {
+            history.push(entry(pc));
+            assert_eq!(history.entries().len(), 3);
+            assert_eq!(history.entries()[2], entry(pc));
+        }
+        assert_eq!(history.entries(), &[entry(7), entry(8), entry(9)]);
+        assert_eq!(history.entries.capacity(), 6);
+    }
+}